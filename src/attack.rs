@@ -0,0 +1,87 @@
+// How many garbage lines a lock sends, following the guideline tables.
+// There is no T-spin detection yet, so only a tetris counts as a "difficult" clear for back-to-back.
+
+// extra lines by combo count (number of consecutive clearing locks minus one)
+pub const COMBO_TABLE: [usize; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+pub const B2B_BONUS: usize = 1;
+pub const PERFECT_CLEAR_BONUS: usize = 10;
+
+pub fn base_attack(lines: usize) -> usize {
+    match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => 4,
+    }
+}
+
+pub fn is_difficult(lines: usize) -> bool {
+    lines >= 4
+}
+
+pub fn combo_bonus(combo: usize) -> usize {
+    COMBO_TABLE[combo.min(COMBO_TABLE.len() - 1)]
+}
+
+// combo and back-to-back state of one player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AttackState {
+    // None after a lock that cleared nothing
+    pub combo: Option<usize>,
+    pub b2b: bool,
+}
+
+impl AttackState {
+    // update the chain with the result of a lock and return the lines to send.
+    pub fn on_lock(&mut self, lines: usize, perfect_clear: bool) -> usize {
+        if lines == 0 {
+            self.combo = None;
+            return 0;
+        }
+        let combo = self.combo.map_or(0, |c| c + 1);
+        self.combo = Some(combo);
+
+        let mut attack = base_attack(lines) + combo_bonus(combo);
+        if is_difficult(lines) {
+            if self.b2b {
+                attack += B2B_BONUS;
+            }
+            self.b2b = true;
+        } else {
+            self.b2b = false;
+        }
+        if perfect_clear {
+            attack += PERFECT_CLEAR_BONUS;
+        }
+        attack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combo_chain() {
+        let mut state = AttackState::default();
+        assert_eq!(state.on_lock(1, false), 0); // combo 0
+        assert_eq!(state.on_lock(1, false), 0); // combo 1
+        assert_eq!(state.on_lock(2, false), 2); // combo 2: 1 + 1
+        assert_eq!(state.on_lock(0, false), 0);
+        assert_eq!(state.combo, None);
+        assert_eq!(state.on_lock(2, false), 1);
+    }
+
+    #[test]
+    fn test_back_to_back() {
+        let mut state = AttackState::default();
+        assert_eq!(state.on_lock(4, false), 4);
+        assert_eq!(state.on_lock(0, false), 0);
+        // b2b survives a non clearing lock but not a small clear
+        assert_eq!(state.on_lock(4, false), 4 + B2B_BONUS);
+        assert_eq!(state.on_lock(0, false), 0);
+        assert_eq!(state.on_lock(1, false), 0);
+        assert!(!state.b2b);
+        assert_eq!(state.on_lock(4, true), 4 + PERFECT_CLEAR_BONUS);
+    }
+}
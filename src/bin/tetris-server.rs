@@ -0,0 +1,17 @@
+// Pairs up `tetris --connect <addr>` clients and relays their versus messages.
+// usage: tetris-server [addr]   (default 0.0.0.0:7777)
+use std::net::TcpListener;
+use tetris::net::{DEFAULT_PORT, run_server};
+
+fn main() {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_PORT));
+    let listener = TcpListener::bind(&addr).unwrap_or_else(|e| {
+        eprintln!("cannot listen on {}: {}", addr, e);
+        std::process::exit(1);
+    });
+    println!("tetris-server listening on {}", listener.local_addr().map(|a| a.to_string()).unwrap_or(addr));
+    if let Err(e) = run_server(listener) {
+        eprintln!("server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
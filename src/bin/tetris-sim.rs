@@ -0,0 +1,67 @@
+// Runs bot games without a window as fast as it can, to compare or tune evaluation weights.
+// usage: tetris-sim [--games N] [--seed S] [--pieces MAX] [--mode NAME] [--no-lookahead]
+//                   [--weights height,lines,holes,bumpiness]
+// Game i uses seed S + i for its pieces and garbage, so two runs with the same options play the same games.
+use std::time::Instant;
+use tetris::bot::{Bot, Weights, play_headless};
+use tetris::game::GameStats;
+use tetris::mode::{ModeStatus, mode_by_name, seeded_mode_by_name};
+use tetris::cli::{arg_value, exit_with, new_game};
+
+fn number<T: std::str::FromStr>(args: &[String], flag: &str, default: T) -> T {
+    match arg_value(args, flag) {
+        Some(v) => v.parse().unwrap_or_else(|_| exit_with(format!("{} needs a number, got '{}'", flag, v))),
+        None => default,
+    }
+}
+
+fn parse_weights(value: &str) -> Weights {
+    let w: Vec<f64> = value.split(',')
+        .map(|v| v.trim().parse().unwrap_or_else(|_| exit_with(format!("bad weight '{}'", v))))
+        .collect();
+    let [aggregate_height, lines, holes, bumpiness] = w[..] else {
+        exit_with("--weights needs 4 numbers: height,lines,holes,bumpiness".to_string());
+    };
+    Weights { aggregate_height, lines, holes, bumpiness }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let games: u64 = number(&args, "--games", 10);
+    let seed: u64 = number(&args, "--seed", 0);
+    let max_pieces: usize = number(&args, "--pieces", 1000);
+    let mode_name = arg_value(&args, "--mode").unwrap_or("endless");
+    let weights = arg_value(&args, "--weights").map(parse_weights).unwrap_or_default();
+    let bot = Bot { weights, lookahead: !args.iter().any(|a| a == "--no-lookahead") };
+    if mode_by_name(mode_name).is_none() {
+        exit_with(format!("unknown mode '{}'", mode_name));
+    }
+
+    let wall = Instant::now();
+    let mut total = GameStats::default();
+    let mut topped_out = 0;
+    for i in 0..games {
+        let game_seed = seed.wrapping_add(i);
+        let mode = seeded_mode_by_name(mode_name, game_seed).unwrap();
+        let start = Instant::now();
+        let mut game = new_game(10, 20, mode, None, None, Some(game_seed), start).unwrap_or_else(|e| exit_with(e));
+        let stats = play_headless(bot, &mut game, start, max_pieces);
+        // a finished sprint or dig also stops early, only count the games lost
+        let over = game.get_status() == ModeStatus::Failed;
+        topped_out += over as usize;
+        println!("game {:>3} seed {:>5}: {:>5} lines, {:>5} pieces, score {:>8}{}",
+            i + 1, game_seed, stats.lines, stats.pieces, stats.score, if over { " (topped out)" } else { "" });
+        total.lines += stats.lines;
+        total.pieces += stats.pieces;
+        total.score += stats.score;
+    }
+
+    let secs = wall.elapsed().as_secs_f64();
+    let n = games.max(1) as f64;
+    println!("{:?}", bot.weights);
+    println!("{} games, {} topped out before {} pieces", games, topped_out, max_pieces);
+    println!("average: {:.1} lines, {:.1} pieces, score {:.0}",
+        total.lines as f64 / n, total.pieces as f64 / n, total.score as f64 / n);
+    println!("throughput: {:.0} pieces/s, {:.2} games/s ({:.2}s)",
+        total.pieces as f64 / secs, games as f64 / secs, secs);
+}
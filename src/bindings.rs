@@ -0,0 +1,139 @@
+// Which keys (or buttons) press which GameCommand, with a small TOML-like config file:
+//
+//     # single player, also what you get before any [section]
+//     move_left = ["Left", "J"]
+//     rotate = "Up"
+//
+//     [player1]
+//     hard_drop = Space
+//
+// One `command = key, ...` per line, quotes and brackets are optional. A command listed in the
+// file replaces all of its default keys, the ones that are not listed keep their defaults.
+// The key type is up to the frontend (minifb keys, gamepad buttons), it only has to turn a name
+// into a key.
+use crate::game::GameCommand;
+
+// the section lines before any [header] belong to
+pub const DEFAULT_SECTION: &str = "single";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings<K> {
+    keys: Vec<(K, GameCommand)>,
+}
+
+impl<K: Copy + PartialEq> Bindings<K> {
+    pub fn new(keys: &[(K, GameCommand)]) -> Self {
+        Self { keys: keys.to_vec() }
+    }
+
+    pub fn keys(&self) -> &[(K, GameCommand)] {
+        &self.keys
+    }
+
+    pub fn keys_for(&self, command: GameCommand) -> impl Iterator<Item = K> + '_ {
+        self.keys.iter().filter(move |(_, c)| *c == command).map(|&(k, _)| k)
+    }
+
+    // one (pressed, command) per command: pressed if any of its keys is down.
+    // Feeding each key on its own would let a released key cancel a held one bound to the same command.
+    pub fn states(&self, is_down: impl Fn(K) -> bool) -> Vec<(bool, GameCommand)> {
+        GameCommand::ALL.iter()
+            .map(|&command| (self.keys_for(command).any(&is_down), command))
+            .collect()
+    }
+
+    // apply the lines of `section` from a config file on top of these bindings
+    pub fn load(&mut self, text: &str, section: &str, key_from_name: impl Fn(&str) -> Option<K>) -> Result<(), String> {
+        for (name, command, keys) in parse(text, key_from_name)? {
+            if name == section {
+                self.keys.retain(|&(_, c)| c != command);
+                self.keys.extend(keys.into_iter().map(|k| (k, command)));
+            }
+        }
+        Ok(())
+    }
+}
+
+// (section, command, keys) for every line of the file.
+// The whole file is checked so a typo in another section is reported too.
+fn parse<K>(text: &str, key_from_name: impl Fn(&str) -> Option<K>) -> Result<Vec<(String, GameCommand, Vec<K>)>, String> {
+    let mut section = DEFAULT_SECTION.to_string();
+    let mut found = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.strip_suffix(']').ok_or_else(|| format!("line {}: missing ']'", line_no))?;
+            section = name.trim().to_string();
+            continue;
+        }
+        let (name, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected 'command = key'", line_no))?;
+        let name = name.trim();
+        let command = GameCommand::from_name(name).filter(|&c| c != GameCommand::None)
+            .ok_or_else(|| format!("line {}: unknown command '{}'", line_no, name))?;
+        let keys = value.trim().trim_start_matches('[').trim_end_matches(']')
+            .split(',')
+            .map(|k| k.trim().trim_matches('"').trim())
+            .filter(|k| !k.is_empty())
+            .map(|k| key_from_name(k).ok_or_else(|| format!("line {}: unknown key '{}'", line_no, k)))
+            .collect::<Result<Vec<K>, String>>()?;
+        if keys.is_empty() {
+            return Err(format!("line {}: no key for '{}'", line_no, name));
+        }
+        found.push((section.clone(), command, keys));
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GameCommand::*;
+
+    fn digit(name: &str) -> Option<u8> {
+        name.parse().ok().filter(|&d: &u8| d < 10)
+    }
+
+    fn defaults() -> Bindings<u8> {
+        Bindings::new(&[(1, MoveLeft), (2, MoveRight), (3, SoftDrop), (4, Rotate), (5, HardDrop)])
+    }
+
+    #[test]
+    fn test_load_overrides_listed_commands() {
+        let text = "# mine\nmove_left = [\"7\", \"8\"]\nrotate = 9 # comment\n\n[player2]\nhard_drop = 0\n";
+        let mut bindings = defaults();
+        bindings.load(text, DEFAULT_SECTION, digit).unwrap();
+        assert_eq!(bindings.keys_for(MoveLeft).collect::<Vec<_>>(), vec![7, 8]);
+        assert_eq!(bindings.keys_for(Rotate).collect::<Vec<_>>(), vec![9]);
+        assert_eq!(bindings.keys_for(HardDrop).collect::<Vec<_>>(), vec![5]);
+
+        let mut second = defaults();
+        second.load(text, "player2", digit).unwrap();
+        assert_eq!(second.keys_for(HardDrop).collect::<Vec<_>>(), vec![0]);
+        assert_eq!(second.keys_for(MoveLeft).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_states_merge_keys() {
+        let mut bindings = defaults();
+        bindings.load("move_left = 1, 7", DEFAULT_SECTION, digit).unwrap();
+        let states = bindings.states(|k| k == 7);
+        assert_eq!(states.len(), GameCommand::ALL.len());
+        assert!(states.contains(&(true, MoveLeft)));
+        assert!(states.contains(&(false, MoveRight)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut bindings = defaults();
+        assert_eq!(bindings.load("move_left = 1\nrotate = X", "single", digit), Err("line 2: unknown key 'X'".to_string()));
+        assert_eq!(bindings.load("jump = 1", "single", digit), Err("line 1: unknown command 'jump'".to_string()));
+        assert!(bindings.load("[player1\nrotate = 1", "single", digit).is_err());
+        assert!(bindings.load("rotate =", "single", digit).is_err());
+        // errors in other sections count too
+        assert!(bindings.load("[player2]\nrotate = 1 2 3", "single", digit).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::game::{Board, Cell, GameCommand, GameState, GameStats, InputState, Pos, Tetromino};
+use crate::pathfind::find_paths;
+
+// Weights of the board evaluation, higher total is better.
+// Defaults are the well known values from Yiyuan Lee's genetic tuning.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub lines: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self { aggregate_height: -0.510066, lines: 0.760666, holes: -0.35663, bumpiness: -0.184483 }
+    }
+}
+
+// one way to lock the piece: where it ends up and the inputs to get there from the spawn.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub piece: Tetromino,
+    pub commands: Vec<GameCommand>,
+    pub score: f64,
+}
+
+fn column_heights(board: &Board) -> Vec<i32> {
+    (0..board.width).map(|x| {
+        (0..board.height)
+            .find(|&y| board.get_cell(Pos { x, y }) != Some(Cell::Empty))
+            .map_or(0, |top| board.height - top)
+    }).collect()
+}
+
+// empty squares with something above them in the same column
+fn count_holes(board: &Board) -> i32 {
+    (0..board.width).map(|x| {
+        let mut covered = false;
+        (0..board.height).filter(|&y| {
+            let empty = board.get_cell(Pos { x, y }) == Some(Cell::Empty);
+            covered |= !empty;
+            covered && empty
+        }).count() as i32
+    }).sum()
+}
+
+pub fn evaluate(board: &Board, lines_cleared: usize, weights: &Weights) -> f64 {
+    let heights = column_heights(board);
+    let aggregate: i32 = heights.iter().sum();
+    let bumpiness: i32 = heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum();
+    weights.aggregate_height * aggregate as f64
+        + weights.lines * lines_cleared as f64
+        + weights.holes * count_holes(board) as f64
+        + weights.bumpiness * bumpiness as f64
+}
+
+// every spot the piece can lock in, tucks and spins included (see pathfind).
+pub fn placements(board: &Board, piece: &Tetromino) -> Vec<Placement> {
+    find_paths(board, piece).into_iter()
+        .map(|path| Placement { piece: path.piece, commands: path.commands, score: 0.0 })
+        .collect()
+}
+
+// the board after locking `piece`, and how many lines it cleared
+pub fn simulate_lock(board: &Board, piece: &Tetromino) -> Option<(Board, usize)> {
+    let mut next = board.clone();
+    if !next.try_place(piece) {
+        return None;
+    }
+    let lines = next.check_clear().len();
+    Some((next, lines))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Bot {
+    pub weights: Weights,
+    // also look at the first piece of the preview
+    pub lookahead: bool,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self { weights: Weights::default(), lookahead: true }
+    }
+}
+
+impl Bot {
+    pub fn new(weights: Weights) -> Self {
+        Self { weights, ..Default::default() }
+    }
+
+    fn best_score(&self, board: &Board, piece: &Tetromino) -> Option<f64> {
+        placements(board, piece).iter()
+            .filter_map(|p| simulate_lock(board, &p.piece))
+            .map(|(next, lines)| evaluate(&next, lines, &self.weights))
+            .max_by(f64::total_cmp)
+    }
+
+    // pick the placement for `current`, queue is the preview (next piece first).
+    pub fn think(&self, board: &Board, current: &Tetromino, queue: &[Tetromino]) -> Option<Placement> {
+        placements(board, current).into_iter()
+            .filter_map(|mut placement| {
+                let (next, lines) = simulate_lock(board, &placement.piece)?;
+                let mut score = evaluate(&next, lines, &self.weights);
+                if self.lookahead
+                    && let Some(following) = queue.first() {
+                    // lines of the second piece only count once, the board already has them
+                    score = self.best_score(&next, following).map_or(score, |s| s + self.weights.lines * lines as f64);
+                }
+                placement.score = score;
+                Some(placement)
+            })
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+    }
+}
+
+// Turns the bot's plan into key presses for GameState::update.
+// Each command is pressed for one frame and released the next, so the debounce sees separate taps.
+pub struct BotPlayer {
+    bot: Bot,
+    plan: VecDeque<GameCommand>,
+    held: Option<GameCommand>,
+    // pieces locked when the plan was made, a new piece means a new plan
+    planned_for: Option<usize>,
+}
+
+impl BotPlayer {
+    pub fn new(bot: Bot) -> Self {
+        Self { bot, plan: VecDeque::new(), held: None, planned_for: None }
+    }
+
+    // (pressed, command) pairs to feed to GameState::update this frame
+    pub fn inputs(&mut self, game: &GameState) -> Vec<(bool, GameCommand)> {
+        if let Some(command) = self.held.take() {
+            return vec![(false, command)];
+        }
+        // nothing to place during a line clear or the entry delay
+        if !game.get_phase().has_piece() {
+            return Vec::new();
+        }
+        let pieces = game.get_stats().pieces;
+        if self.planned_for != Some(pieces) {
+            self.planned_for = Some(pieces);
+            self.plan = self.bot.think(&game.board, &game.current_tetris, &game.get_queue())
+                .map(|p| p.commands.into())
+                .unwrap_or_else(|| VecDeque::from([GameCommand::HardDrop]));
+        }
+        match self.plan.pop_front() {
+            Some(command) => {
+                self.held = Some(command);
+                vec![(true, command)]
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+// one simulated frame, 60 per second like the window
+pub const FRAME: Duration = Duration::from_millis(16);
+
+// Plays `game` with the bot and no window. The clock is `start` plus 16ms per frame, never the
+// wall clock, so with a seeded generator the same game is played every time.
+// Stops at game over or once `max_pieces` are locked.
+pub fn play_headless(bot: Bot, game: &mut GameState, start: Instant, max_pieces: usize) -> GameStats {
+    let mut player = BotPlayer::new(bot);
+    let mut now = start;
+    while !game.get_game_over() && game.get_stats().pieces < max_pieces {
+        now += FRAME;
+        game.update_frame(&InputState::from_states(&player.inputs(game)), now);
+    }
+    game.get_stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{RandomGenerator, TetrominoKind};
+    use crate::randomizer::SequenceGenerator;
+
+    #[test]
+    fn test_evaluate_features() {
+        let board = Board::from_rows(&[
+            "....",
+            ".G..",
+            "G.GG",
+        ]).unwrap();
+        assert_eq!(column_heights(&board), vec![1, 2, 1, 1]);
+        assert_eq!(count_holes(&board), 1);
+        let weights = Weights { aggregate_height: 1.0, lines: 0.0, holes: 10.0, bumpiness: 100.0 };
+        assert_eq!(evaluate(&board, 0, &weights), 5.0 + 10.0 + 200.0);
+    }
+
+    #[test]
+    fn test_placements_are_unique() {
+        let board = Board::from_rows(&["..........", "..........", "..........", ".........."]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::O, Pos { x: 5, y: 1 });
+        // O has one shape and fits in 9 columns
+        assert_eq!(placements(&board, &piece).len(), 9);
+        let piece = Tetromino::new(TetrominoKind::I, Pos { x: 5, y: 1 });
+        assert_eq!(placements(&board, &piece).len(), 7 + 10);
+    }
+
+    #[test]
+    fn test_bot_takes_the_tetris() {
+        let mut rows = vec![".........."; 4];
+        rows.extend(["GGGGGG.GGG"; 4]);
+        let board = Board::from_rows(&rows).unwrap();
+        let piece = Tetromino::new(TetrominoKind::I, Pos { x: 5, y: 1 });
+        let best = Bot::default().think(&board, &piece, &[]).unwrap();
+        let (_, lines) = simulate_lock(&board, &best.piece).unwrap();
+        assert_eq!(lines, 4);
+        assert_eq!(best.commands.last(), Some(&GameCommand::HardDrop));
+    }
+
+    #[test]
+    fn test_bot_player_survives() {
+        let start = Instant::now();
+        let mut game = GameState::new(10, 20, start, Box::new(SequenceGenerator::parse("OTJLSZI").unwrap()));
+        // no lookahead, keeps the debug build test fast
+        let stats = play_headless(Bot { lookahead: false, ..Default::default() }, &mut game, start, 100);
+        assert!(!game.get_game_over());
+        assert_eq!(stats.pieces, 100);
+        assert!(stats.lines >= 30);
+    }
+
+    #[test]
+    fn test_headless_is_deterministic() {
+        let bot = Bot { lookahead: false, ..Default::default() };
+        let run = || {
+            let start = Instant::now();
+            let mut game = GameState::new(10, 20, start, Box::new(RandomGenerator::with_seed(7)));
+            play_headless(bot, &mut game, start, 30)
+        };
+        let first = run();
+        assert_eq!(first, run());
+        assert_eq!(first.elapsed.as_millis() % FRAME.as_millis(), 0);
+    }
+}
@@ -0,0 +1,275 @@
+// NES Tetris (1989) as a preset over GameState: no hold, no hard drop, one next piece, the NES rotation (no kicks),
+// the NES randomizer, gravity counted in frames per row, DAS 16/6 frames and the NES score table.
+// Levels go up every 10 lines from the start level, the NES start level transition is left out.
+use std::time::Duration;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::game::{GameState, GameStats, Pos, RotationSystem, Rules, TetrisGenerator, Tetromino, TetrominoKind};
+use crate::mode::{GameMode, ModeStatus, format_time};
+
+pub const NES_NAME: &str = "NES";
+// the NTSC NES runs at 60.0988 frames per second
+pub const NES_FRAME: Duration = Duration::from_nanos(16_639_267);
+pub const NES_DAS: u32 = 16;
+pub const NES_ARR: u32 = 6;
+// soft drop: a row every second frame
+pub const NES_SOFT_DROP: u32 = 2;
+// entry delay and line clear animation, roughly what the NES takes
+pub const NES_ARE: u32 = 10;
+pub const NES_LINE_CLEAR: u32 = 18;
+
+pub fn nes_frames(frames: u32) -> Duration {
+    NES_FRAME * frames
+}
+
+// frames per row at each level, level 29 and up is the kill screen
+pub fn nes_gravity(level: usize) -> Duration {
+    let frames = match level {
+        0 => 48,
+        1 => 43,
+        2 => 38,
+        3 => 33,
+        4 => 28,
+        5 => 23,
+        6 => 18,
+        7 => 13,
+        8 => 8,
+        9 => 6,
+        10..=12 => 5,
+        13..=15 => 4,
+        16..=18 => 3,
+        19..=28 => 2,
+        _ => 1,
+    };
+    nes_frames(frames)
+}
+
+// 40, 100, 300, 1200 times the level plus one
+pub fn nes_line_score(lines: usize, level: usize) -> usize {
+    let points = match lines {
+        0 => 0,
+        1 => 40,
+        2 => 100,
+        3 => 300,
+        _ => 1200,
+    };
+    points * (level + 1)
+}
+
+// The NES randomizer: roll one of 8 slots, and if that is the empty 8th slot or the same piece as
+// last time roll once more among the 7 pieces, which sticks. Repeats are rare but possible.
+pub struct NesGenerator<R: Rng> {
+    rng: R,
+    last: Option<TetrominoKind>,
+}
+
+impl<R: Rng> TetrisGenerator for NesGenerator<R> {
+    fn next(&mut self, x: i32, y: i32) -> Tetromino {
+        let roll = self.rng.random_range(0..8);
+        let kind = match TetrominoKind::ALL.get(roll) {
+            Some(&kind) if Some(kind) != self.last => kind,
+            _ => TetrominoKind::ALL[self.rng.random_range(0..7)],
+        };
+        self.last = Some(kind);
+        Tetromino::new(kind, Pos { x, y })
+    }
+}
+
+impl NesGenerator<ThreadRng> {
+    pub fn new() -> Self {
+        Self { rng: rand::rng(), last: None }
+    }
+}
+
+impl Default for NesGenerator<ThreadRng> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// same seed, same piece sequence
+impl NesGenerator<StdRng> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), last: None }
+    }
+}
+
+// the mode half of the preset: NES gravity and scoring, played until topping out
+pub struct Classic {
+    start_level: usize,
+}
+
+impl Classic {
+    pub fn new(start_level: usize) -> Self {
+        Self { start_level }
+    }
+}
+
+impl Default for Classic {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl GameMode for Classic {
+    fn name(&self) -> &'static str {
+        NES_NAME
+    }
+    fn check(&self, _stats: &GameStats) -> ModeStatus {
+        ModeStatus::Playing
+    }
+    fn summary(&self, stats: &GameStats) -> String {
+        format!("level {}, {} lines, score {} in {}", stats.level, stats.lines, stats.score, format_time(stats.elapsed))
+    }
+    fn start_level(&self) -> usize {
+        self.start_level
+    }
+    fn gravity(&self, level: usize) -> Option<Duration> {
+        Some(nes_gravity(level))
+    }
+    fn line_score(&self, lines: usize, level: usize) -> usize {
+        nes_line_score(lines, level)
+    }
+    // the NES push down: a point a row, if the key is still down when the piece locks
+    fn push_down_score(&self, rows: usize) -> usize {
+        rows
+    }
+}
+
+// the rest of the preset, for a game created with the Classic mode
+pub fn apply_nes_rules(game: &mut GameState) {
+    game.set_rules(Rules {
+        hold: false,
+        hard_drop: false,
+        rotation: RotationSystem::Nrs,
+        preview: 1,
+        soft_drop: nes_frames(NES_SOFT_DROP),
+    });
+    game.set_das(nes_frames(NES_DAS), nes_frames(NES_ARR));
+    game.set_entry_delay(nes_frames(NES_ARE));
+    game.set_line_clear_delay(nes_frames(NES_LINE_CLEAR));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameCommand, create_custom_game};
+    use std::time::Instant;
+
+    fn nes_game(start: Instant) -> GameState {
+        let mut game = create_custom_game(10, 20, start, Box::new(Classic::default()), Box::new(NesGenerator::with_seed(7))).unwrap();
+        apply_nes_rules(&mut game);
+        game
+    }
+
+    #[test]
+    fn test_nes_generator() {
+        let mut a = NesGenerator::with_seed(1);
+        let mut b = NesGenerator::with_seed(1);
+        let kinds: Vec<_> = (0..700).map(|_| a.next(5, 1).kind).collect();
+        assert!(kinds.iter().all(|&k| b.next(5, 1).kind == k));
+        // every piece shows up, and the reroll makes repeats much rarer than 1 in 7
+        for kind in TetrominoKind::ALL {
+            assert!(kinds.contains(&kind));
+        }
+        let repeats = kinds.windows(2).filter(|w| w[0] == w[1]).count();
+        assert!(repeats < 700 / 14, "{} repeats", repeats);
+    }
+
+    #[test]
+    fn test_nes_scoring_and_gravity() {
+        assert_eq!(nes_line_score(4, 0), 1200);
+        assert_eq!(nes_line_score(1, 9), 400);
+        assert_eq!(nes_gravity(0), nes_frames(48));
+        assert_eq!(nes_gravity(19), nes_gravity(28));
+        assert!(nes_gravity(29) < nes_gravity(28));
+    }
+
+    #[test]
+    fn test_nes_rules() {
+        let start = Instant::now();
+        let mut game = nes_game(start);
+        assert_eq!(game.get_stats().level, 0);
+        assert_eq!(game.get_queue().len(), 1);
+        assert_eq!(game.hold_swap(), None);
+        let before = game.current_tetris;
+        // no hard drop and no hold
+        assert!(!game.update(true, GameCommand::HardDrop, start));
+        assert!(!game.update(true, GameCommand::Hold, start));
+        assert_eq!(game.current_tetris, before);
+        assert_eq!(game.get_stats().pieces, 0);
+    }
+
+    #[test]
+    fn test_nes_soft_drop_rate() {
+        let start = Instant::now();
+        let mut game = nes_game(start);
+        let top = game.current_tetris.pos().y;
+        // 20 frames of a 60Hz loop holding the key: a row on every second one
+        for frame in 0..20 {
+            game.update(true, GameCommand::SoftDrop, start + Duration::from_micros(16_667) * frame);
+        }
+        assert_eq!(game.current_tetris.pos().y - top, 10);
+    }
+
+    #[test]
+    fn test_nes_i_stands_up_at_spawn() {
+        let start = Instant::now();
+        let mut game = create_custom_game(10, 20, start, Box::new(Classic::default()),
+            Box::new(crate::randomizer::SequenceGenerator::parse("I").unwrap())).unwrap();
+        apply_nes_rules(&mut game);
+        assert!(game.update(true, GameCommand::Rotate, start));
+        assert_eq!(game.current_tetris.rotation_index(), 1);
+        assert!(game.current_tetris.world_cells().iter().all(|c| c.y >= 0));
+    }
+
+    #[test]
+    fn test_nes_push_down() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut game = nes_game(start);
+        // three rows above the floor, held down to the lock
+        let landed = crate::game::try_hard_drop(&game.board, &game.current_tetris).unwrap();
+        let up = Pos { x: landed.pos().x, y: landed.pos().y - 3 };
+        game.current_tetris = Tetromino::new(landed.kind, up).with_rotation_system(RotationSystem::Nrs);
+        let mut ms = 0;
+        while game.get_stats().pieces == 0 {
+            game.update(true, GameCommand::SoftDrop, at(ms));
+            ms += 130;
+        }
+        assert_eq!(game.get_stats().score, 3);
+
+        // let go before the lock: nothing
+        // the next piece spawns at the top after the entry delay
+        while game.current_tetris.pos().y > 5 {
+            ms += 100;
+            game.update(false, GameCommand::None, at(ms));
+        }
+        while game.current_tetris.pos().y < 8 {
+            ms += 130;
+            game.update(true, GameCommand::SoftDrop, at(ms));
+        }
+        game.update(false, GameCommand::SoftDrop, at(ms));
+        while game.get_stats().pieces == 1 {
+            ms += 100;
+            game.update(false, GameCommand::None, at(ms));
+        }
+        assert_eq!(game.get_stats().score, 3);
+    }
+
+    #[test]
+    fn test_nes_rotation_has_no_kicks() {
+        let start = Instant::now();
+        let mut game = nes_game(start);
+        assert_eq!(game.current_tetris.rotation_system(), RotationSystem::Nrs);
+        // an I standing against the left wall cannot turn flat
+        let standing = Tetromino::new(TetrominoKind::I, Pos { x: 5, y: 2 }).with_rotation_system(RotationSystem::Nrs);
+        let t = crate::game::try_rotate(&game.board, &standing).unwrap();
+        let t = (0..6).fold(t, |t, _| crate::game::try_horizon(&game.board, &t, true).unwrap_or(t));
+        game.current_tetris = t;
+        assert!(!game.update(true, GameCommand::Rotate, start));
+        assert_eq!(game.current_tetris, t);
+    }
+}
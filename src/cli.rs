@@ -0,0 +1,61 @@
+// Command line helpers shared by the binaries (main.rs, bin/tetris-sim.rs).
+use std::time::Instant;
+
+use crate::classic::{NES_NAME, apply_nes_rules};
+use crate::game::{GameState, create_custom_game};
+use crate::mode::GameMode;
+use crate::randomizer::{GENERATOR_NAMES, SequenceGenerator, generator_by_name};
+
+// the value after `flag`, "" if the flag is last, None if it is not given
+pub fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let i = args.iter().position(|a| a == flag)?;
+    Some(args.get(i + 1).map(String::as_str).unwrap_or(""))
+}
+
+// bad arguments: print why and quit
+pub fn exit_with(msg: String) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(2);
+}
+
+// a game of `mode` with its pieces from the `randomizer` generator (see randomizer.rs), seeded if
+// there is a seed. The NES mode brings its own randomizer and rules (see classic.rs).
+// A `sequence` comes first: it loops, or goes on with the randomizer when one was asked for.
+pub fn new_game(width: i32, height: i32, mode: Box<dyn GameMode>, randomizer: Option<&str>,
+    sequence: Option<SequenceGenerator>, seed: Option<u64>, start: Instant) -> Result<GameState, String> {
+    let nes = mode.name() == NES_NAME;
+    let name = randomizer.unwrap_or(if nes { "nes" } else { "random" });
+    let mut generator = generator_by_name(name, seed).ok_or_else(|| format!(
+        "unknown randomizer '{}', expected one of {}", name, GENERATOR_NAMES.join(", ")))?;
+    if let Some(pieces) = sequence {
+        generator = if randomizer.is_some() { Box::new(pieces.then(generator)) } else { Box::new(pieces) };
+    }
+    let mut game = create_custom_game(width, height, start, mode, generator)?;
+    if nes {
+        apply_nes_rules(&mut game);
+    }
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arg_value() {
+        let args: Vec<String> = ["tetris", "--mode", "sprint", "--seed"].iter().map(|a| a.to_string()).collect();
+        assert_eq!(arg_value(&args, "--mode"), Some("sprint"));
+        assert_eq!(arg_value(&args, "--seed"), Some(""));
+        assert_eq!(arg_value(&args, "--time"), None);
+    }
+
+    #[test]
+    fn test_new_game() {
+        let start = Instant::now();
+        let nes = crate::mode::mode_by_name("nes").unwrap();
+        let game = new_game(10, 20, nes, None, None, Some(1), start).unwrap();
+        assert!(!game.get_rules().hard_drop);
+        let endless = Box::new(crate::mode::Endless);
+        assert!(new_game(10, 20, endless, Some("nope"), None, None, start).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::game::{Board, GameCommand, Tetromino, apply_command, try_hard_drop};
+use crate::pathfind::cells_key;
+
+// One key press as a finesse trainer counts it: holding a key is a single input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FinesseMove {
+    TapLeft,
+    TapRight,
+    // hold until the piece hits the wall (or the stack)
+    DasLeft,
+    DasRight,
+    Rotate,
+    // hold soft drop until the piece is on the floor
+    SoftDrop,
+}
+
+impl FinesseMove {
+    const ALL: [FinesseMove; 6] = [
+        FinesseMove::TapLeft,
+        FinesseMove::TapRight,
+        FinesseMove::DasLeft,
+        FinesseMove::DasRight,
+        FinesseMove::Rotate,
+        FinesseMove::SoftDrop,
+    ];
+
+    fn apply(self, board: &Board, t: &Tetromino) -> Option<Tetromino> {
+        use FinesseMove::*;
+        let slide = |command| {
+            let mut at = apply_command(board, t, command)?;
+            while let Some(next) = apply_command(board, &at, command) {
+                at = next;
+            }
+            Some(at)
+        };
+        match self {
+            TapLeft => apply_command(board, t, GameCommand::MoveLeft),
+            TapRight => apply_command(board, t, GameCommand::MoveRight),
+            DasLeft => slide(GameCommand::MoveLeft),
+            DasRight => slide(GameCommand::MoveRight),
+            Rotate => apply_command(board, t, GameCommand::Rotate),
+            SoftDrop => slide(GameCommand::SoftDrop),
+        }
+    }
+}
+
+// Fewest key presses that take `piece` from the spawn to where `target` locked.
+// The final hard drop is not part of the result, both sides of the comparison leave it out.
+pub fn optimal_inputs(board: &Board, piece: &Tetromino, target: &Tetromino) -> Option<Vec<FinesseMove>> {
+    let key = cells_key(target);
+    let mut seen = HashSet::from([*piece]);
+    let mut todo = VecDeque::from([(*piece, Vec::new())]);
+    while let Some((current, moves)) = todo.pop_front() {
+        if try_hard_drop(board, &current).is_some_and(|dropped| cells_key(&dropped) == key) {
+            return Some(moves);
+        }
+        for step in FinesseMove::ALL {
+            if let Some(next) = step.apply(board, &current)
+                && seen.insert(next) {
+                let mut path = moves.clone();
+                path.push(step);
+                todo.push_back((next, path));
+            }
+        }
+    }
+    None
+}
+
+// extra presses compared to the optimal input, 0 for a clean piece.
+// Placements the search cannot reach (moved by gravity into a spot, say) are never faults.
+pub fn faults(board: &Board, piece: &Tetromino, target: &Tetromino, inputs_used: usize) -> usize {
+    optimal_inputs(board, piece, target).map_or(0, |best| inputs_used.saturating_sub(best.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Pos, TetrominoKind};
+    use crate::pathfind::find_paths;
+
+    fn empty() -> Board {
+        Board::from_rows(&[".........."; 6]).unwrap()
+    }
+
+    fn landing(board: &Board, piece: &Tetromino, commands: &[GameCommand]) -> Tetromino {
+        let at = commands.iter().fold(*piece, |t, &c| apply_command(board, &t, c).unwrap());
+        try_hard_drop(board, &at).unwrap()
+    }
+
+    #[test]
+    fn test_das_to_the_wall() {
+        let board = empty();
+        let piece = Tetromino::new(TetrominoKind::O, Pos { x: 5, y: 1 });
+        let target = landing(&board, &piece, &[GameCommand::MoveLeft; 5]);
+        assert_eq!(optimal_inputs(&board, &piece, &target), Some(vec![FinesseMove::DasLeft]));
+        // tapping five times is four presses too many
+        assert_eq!(faults(&board, &piece, &target, 5), 4);
+        assert_eq!(faults(&board, &piece, &target, 1), 0);
+    }
+
+    #[test]
+    fn test_das_then_tap_back() {
+        let board = empty();
+        let piece = Tetromino::new(TetrominoKind::T, Pos { x: 5, y: 1 });
+        // T flat at columns 1..3: DAS left then tap right beats four taps
+        let target = landing(&board, &piece, &[GameCommand::MoveLeft; 3]);
+        assert_eq!(optimal_inputs(&board, &piece, &target).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_drop_in_place_is_free() {
+        let board = empty();
+        let piece = Tetromino::new(TetrominoKind::I, Pos { x: 5, y: 1 });
+        let target = landing(&board, &piece, &[]);
+        assert_eq!(optimal_inputs(&board, &piece, &target), Some(vec![]));
+    }
+
+    #[test]
+    fn test_every_placement_has_an_answer() {
+        let board = Board::from_rows(&["..........", "..........", "..........", "GG....G..G"]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::L, Pos { x: 5, y: 1 });
+        for path in find_paths(&board, &piece) {
+            let best = optimal_inputs(&board, &piece, &path.piece).unwrap();
+            // never worse than the path finder that taps every cell
+            assert!(best.len() < path.commands.len());
+        }
+    }
+}
@@ -5,17 +5,32 @@ const CYAN: u32  = 0x00FFFF;
 const YELLOW: u32 = 0xFFFF00;
 const ORANGE: u32 = 0xFFA500;
 const PURPLE: u32 = 0x800080;
+const GRAY: u32 = 0x808080;
+const SLATE: u32 = 0x2F4F4F;
 const LOCK_DELAY: u64 = 500;
+const SOFT_DROP: Duration = Duration::from_millis(120);
+const LINES_PER_LEVEL: usize = 10;
+const NEXT_PREVIEW: usize = 5;
 
-use std::time::{Instant};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::attack::AttackState;
+use crate::finesse;
+use crate::garbage::GarbageQueue;
+use crate::mode::{Endless, GameMode, GameResult, ModeStatus};
+pub use crate::rotation::RotationSystem;
 
 // Clone represents the general ability to duplicate a value. 
 // Copy is a subset of Clone for types that can be bitwise copied. 
 // Rust requires Copy types to also implement Clone for trait consistency.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TetrominoKind {I, O, T, J, L, S, Z}
 
 impl TetrominoKind {
+    pub const ALL: [TetrominoKind; 7] = [TetrominoKind::I, TetrominoKind::O, TetrominoKind::T,
+        TetrominoKind::J, TetrominoKind::L, TetrominoKind::S, TetrominoKind::Z];
+
     pub fn color(&self) -> u32 {
         use TetrominoKind::*;
         match self {
@@ -30,10 +45,10 @@ impl TetrominoKind {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Rotation {R0, R90, R180, R270 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Pos { pub x: i32, pub y: i32 }
 
 impl Rotation {
@@ -67,7 +82,8 @@ impl Add for Pos {
 
 use std::ops::Mul;
 
-use crate::input::{ConstMotion, LockMgr, MotionState};
+use crate::input::{ConstMotion, LockMgr, MotionState, ShiftState};
+pub use crate::input::ShiftPriority;
 // 實作 Pos * i32
 impl Mul<i32> for Pos {
     type Output = Pos;
@@ -80,67 +96,40 @@ impl Mul<i32> for Pos {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Tetromino {
     pub kind: TetrominoKind,
     pos: Pos,
     rot: Rotation,
+    system: RotationSystem,
 }
 
 impl Tetromino {
     pub fn new(kind: TetrominoKind, pos: Pos) -> Self {
         // Field Init Shorthand: Field names can be omitted if they match the argument names.
-        Self { kind, pos, rot: Rotation::R0 }
+        Self { kind, pos, rot: Rotation::R0, system: RotationSystem::default() }
     }
 
-    fn relative_cells(kind: TetrominoKind, rot: Rotation) -> [Pos;4]/*fixed length array on stack / no heap */ {
-        use TetrominoKind::*;
-        use Rotation::*;
-        match kind {
-            I => match rot {
-                R0   => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:2, y:0}],
-                R90  => [Pos{x:1, y:-1}, Pos{x:1, y:0}, Pos{x:1, y:1}, Pos{x:1, y:2}],
-                R180 => [Pos{x:-1, y:1}, Pos{x:0, y:1}, Pos{x:1, y:1}, Pos{x:2, y:1}],
-                R270 => [Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:0, y:1}, Pos{x:0, y:2}],
-            },
-            O => {
-                [Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:0, y:1}, Pos{x:1, y:1}]
-            },
-            T => match rot {
-                R0   => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:0, y:-1}],
-                R90  => [Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:0, y:1}],
-                R180 => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:0, y:1}],
-                R270 => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:0, y:-1}, Pos{x:0, y:1}],
-            },
-            J => match rot {
-                R0   => [Pos{x:-1, y:-1}, Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}],
-                R90  => [Pos{x:0, y:-1}, Pos{x:1, y:-1}, Pos{x:0, y:0}, Pos{x:0, y:1}],
-                R180 => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:1, y:1}],
-                R270 => [Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:0, y:1}, Pos{x:-1, y:1}],
-            },
-            L => match rot {
-                R0   => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:1, y:-1}],
-                R90  => [Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:0, y:1}, Pos{x:1, y:1}],
-                R180 => [Pos{x:-1, y:1}, Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:1, y:0}],
-                R270 => [Pos{x:-1, y:-1}, Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:0, y:1}],
-            },
-            S => match rot {
-                R0   => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:0, y:-1}, Pos{x:1, y:-1}],
-                R90  => [Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:1, y:0}, Pos{x:1, y:1}],
-                R180 => [Pos{x:-1, y:1}, Pos{x:0, y:1}, Pos{x:0, y:0}, Pos{x:1, y:0}],
-                R270 => [Pos{x:-1, y:-1}, Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:0, y:1}],
-            },
-            Z => match rot {
-                R0   => [Pos{x:-1, y:-1}, Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:1, y:0}],
-                R90  => [Pos{x:1, y:-1}, Pos{x:1, y:0}, Pos{x:0, y:0}, Pos{x:0, y:1}],
-                R180 => [Pos{x:-1, y:0}, Pos{x:0, y:0}, Pos{x:0, y:1}, Pos{x:1, y:1}],
-                R270 => [Pos{x:0, y:-1}, Pos{x:0, y:0}, Pos{x:-1, y:0}, Pos{x:-1, y:1}],
-            },
-        }
+    // the same piece with the shapes and kicks of another rotation system
+    pub fn with_rotation_system(self, system: RotationSystem) -> Self {
+        Self { system, ..self }
+    }
+
+    pub fn rotation_system(&self) -> RotationSystem {
+        self.system
+    }
+
+    pub(crate) fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    // 0..4, clockwise from the spawn orientation
+    pub(crate) fn rotation_index(&self) -> usize {
+        self.rot as usize
     }
 
     pub fn world_cells(&self) -> [Pos;4] {
-        let rel = Self::relative_cells(self.kind/*Copy */, self.rot /*Copy*/);
+        let rel = self.system.cells(self.kind/*Copy */, self.rotation_index());
 
     //     pub trait Add<Rhs = Self> {
     //     type Output;
@@ -156,7 +145,8 @@ impl Tetromino {
     // &self is equivalent to self: &Self.
     // it is syntactic sugar
     // Original form: fn rotate_cw(self: &Self) -> Tetromino
-    fn rotate_cw(&self) -> Tetromino {
+    // Turns in place, no kicks and no board: see try_rotate for a turn in the game.
+    pub fn rotate_cw(&self) -> Tetromino {
         // ..*self
         // In Rust, the struct update syntax is used to create a new struct instance based on an
         // existing one, allowing you to explicitly set new values for some fileds while copying the remaining
@@ -164,104 +154,232 @@ impl Tetromino {
         Tetromino { rot: self.rot.cw(), ..*self/*here we need to use deref because self is a &Self */ }
     }
 
-    fn rotate_ccw(&self) -> Tetromino {
+    pub fn rotate_ccw(&self) -> Tetromino {
         Tetromino { rot: self.rot.ccw(), ..*self }
     }
 
 } 
 
 
+// what a board square holds.
+// Garbage rows come from the dig mode and versus attacks, they clear like any other row.
+// Wall is a solid block that never goes away: a row holding a wall is never cleared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Piece(TetrominoKind),
+    Garbage,
+    Wall,
+}
+
+impl Cell {
+    pub fn is_empty(&self) -> bool {
+        *self == Cell::Empty
+    }
+
+    // None for empty squares, the renderer leaves them as background.
+    pub fn color(&self) -> Option<u32> {
+        match self {
+            Cell::Empty => None,
+            Cell::Piece(kind) => Some(kind.color()),
+            Cell::Garbage => Some(GRAY),
+            Cell::Wall => Some(SLATE),
+        }
+    }
+
+    pub fn to_char(&self) -> char {
+        use TetrominoKind::*;
+        match self {
+            Cell::Empty => '.',
+            Cell::Garbage => 'G',
+            Cell::Wall => 'W',
+            Cell::Piece(I) => 'I',
+            Cell::Piece(O) => 'O',
+            Cell::Piece(T) => 'T',
+            Cell::Piece(J) => 'J',
+            Cell::Piece(L) => 'L',
+            Cell::Piece(S) => 'S',
+            Cell::Piece(Z) => 'Z',
+        }
+    }
+
+    // '.' empty, 'G' garbage, 'W' wall, piece letters for pieces.
+    pub fn from_char(c: char) -> Option<Cell> {
+        use TetrominoKind::*;
+        Some(match c.to_ascii_uppercase() {
+            '.' => Cell::Empty,
+            'G' => Cell::Garbage,
+            'W' => Cell::Wall,
+            'I' => Cell::Piece(I),
+            'O' => Cell::Piece(O),
+            'T' => Cell::Piece(T),
+            'J' => Cell::Piece(J),
+            'L' => Cell::Piece(L),
+            'S' => Cell::Piece(S),
+            'Z' => Cell::Piece(Z),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Board {
     // It is recommended to use i32 instead of u32 for members used in indexing and coordinate math.
     pub width: i32,
     pub height: i32,
-    pub cells : Vec<Option<TetrominoKind>>,
+    pub cells : Vec<Cell>,
 }
 
 
+// every piece fits at the spawn of a board this wide (the I sticks out 2 to the right of the middle)
+pub const MIN_BOARD_WIDTH: i32 = 5;
+pub const MIN_BOARD_HEIGHT: i32 = 4;
+// more than anyone can play, and the index math stays far from overflowing
+pub const MAX_BOARD_SIZE: i32 = 100;
+
+// the board sizes a game can be built with, the factories below refuse the others
+pub fn check_board_size(width:i32, height:i32) -> Result<(), String> {
+    if !(MIN_BOARD_WIDTH..=MAX_BOARD_SIZE).contains(&width) {
+        return Err(format!("board width {} is out of range {}..={}", width, MIN_BOARD_WIDTH, MAX_BOARD_SIZE));
+    }
+    if !(MIN_BOARD_HEIGHT..=MAX_BOARD_SIZE).contains(&height) {
+        return Err(format!("board height {} is out of range {}..={}", height, MIN_BOARD_HEIGHT, MAX_BOARD_SIZE));
+    }
+    Ok(())
+}
+
 impl Board {
-    fn new(width:i32, height:i32) -> Self {
-        Self { width, height, cells: vec![None; (width*height) as usize] }
+    pub(crate) fn new(width:i32, height:i32) -> Self {
+        Self { width, height, cells: vec![Cell::Empty; (width*height) as usize] }
+    }
+
+    // where new pieces appear: the middle column, one row down so the top of the T fits
+    pub fn spawn_pos(&self) -> Pos {
+        Pos { x: self.width / 2, y: 1 }
+    }
+
+    // build a board from text rows (see Cell::from_char), top row first.
+    // handy for pre-placed layouts and tests.
+    pub fn from_rows(rows:&[&str]) -> Option<Self> {
+        let width = rows.first()?.chars().count();
+        if width == 0 || rows.iter().any(|row| row.chars().count() != width) {
+            return None;
+        }
+        let cells = rows.iter().flat_map(|row| row.chars().map(Cell::from_char))
+            .collect::<Option<Vec<Cell>>>()?;
+        Some(Self { width: width as i32, height: rows.len() as i32, cells })
     }
-    fn try_place(&mut self, t:&Tetromino) -> bool
+
+    pub fn get_cell(&self, pos:Pos) -> Option<Cell> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.width || pos.y >= self.height {
+            return None;
+        }
+        Some(self.cells[(pos.y * self.width + pos.x) as usize])
+    }
+    pub(crate) fn try_place(&mut self, t:&Tetromino) -> bool
     {
         if !self.can_place(t) { return false; }
-        t.world_cells().into_iter().for_each(|pos| self.set_occupied(pos, Some(t.kind)));
+        t.world_cells().into_iter().for_each(|pos| self.set_occupied(pos, Cell::Piece(t.kind)));
         true
     }
 
 
-    // clears full lines and returns the score.
-    fn check_clear(&mut self) -> usize {
+    // clears full lines and returns which rows they were, top first.
+    pub(crate) fn check_clear(&mut self) -> Vec<usize> {
 
         // better to create a new usize here
         let width = self.width as usize;
-
+        let full = self.full_rows();
 
         // collect the remaining lines that are not full
-        let new_cells:Vec<Option<TetrominoKind>> =
+        let new_cells:Vec<Cell> =
             self.cells.chunks(width) /*iterm = &[T] */
-            .filter(|row /*filter will add & to &[T]*/ | {
-                row.iter().any(|cell| cell.is_none())
-            })
-            .flatten()
-            .copied() /*&Option to Option */
+            .enumerate()
+            .filter(|(y, _)| !full.contains(y))
+            .flat_map(|(_, row)| row.iter().copied()) /*&Cell to Cell */
             .collect();
 
-        let n_cleared_lines= self.height as usize - (new_cells.len()/width);
         // padding from the top
-        let mut paddings:Vec<Option<TetrominoKind>>  = vec![None; width * n_cleared_lines];
+        let mut paddings:Vec<Cell>  = vec![Cell::Empty; width * full.len()];
         paddings.extend(new_cells);
         self.cells = paddings;
-        n_cleared_lines as usize
+        full
+    }
+
+    // rows that are full (walls are never cleared) and would go with the next check_clear, top first
+    pub fn full_rows(&self) -> Vec<usize> {
+        self.cells.chunks(self.width as usize).enumerate()
+            .filter(|(_, row)| row.iter().all(|cell| !cell.is_empty() && *cell != Cell::Wall))
+            .map(|(y, _)| y)
+            .collect()
     }
 
-    fn can_place(&self, t:&Tetromino) -> bool {
+    pub(crate) fn can_place(&self, t:&Tetromino) -> bool {
         !t.world_cells().into_iter().any(|pos|self.is_occupied(pos))
     }
 
-    fn is_occupied(&self, pos:Pos) -> bool {
+    pub(crate) fn is_occupied(&self, pos:Pos) -> bool {
         if pos.x < 0 || pos.y < 0 || pos.x >= self.width || pos.y >= self.height {
             return true;
         }
-        self.cells[(pos.y * self.width + pos.x) as usize] != None
+        !self.cells[(pos.y * self.width + pos.x) as usize].is_empty()
     }
 
-    fn set_occupied(&mut self, pos: Pos, value: Option<TetrominoKind>) {
+    fn set_occupied(&mut self, pos: Pos, value: Cell) {
         if pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height {
             self.cells[(pos.y * self.width + pos.x) as usize] = value;
         }
     }
-}
 
+    // push a garbage row with one hole in from the bottom, everything else moves up.
+    // returns false if blocks were pushed out of the top.
+    pub fn push_garbage_row(&mut self, hole:i32) -> bool {
+        let width = self.width as usize;
+        let overflow = self.cells[..width].iter().any(|cell| !cell.is_empty());
+        self.cells.drain(..width);
+        self.cells.extend((0..self.width).map(|x| if x == hole {Cell::Empty} else {Cell::Garbage}));
+        !overflow
+    }
+
+    // number of rows that still contain garbage
+    pub fn garbage_rows(&self) -> usize {
+        self.cells.chunks(self.width as usize)
+            .filter(|row| row.contains(&Cell::Garbage))
+            .count()
+    }
+}
 
-const KICKS : [Pos;5] = [
-    Pos {x:0, y:0},
-    Pos {x:-1, y:0},
-    Pos {x:1, y:0},
-    Pos {x:0, y:-1},
-    Pos {x:0, y:1},
-];
 
-fn rotate_with_kick(board:&Board, t:&Tetromino) -> Option<Tetromino> 
+// turn clockwise, trying the kicks of the piece's rotation system
+pub(crate) fn rotate_with_kick(board:&Board, t:&Tetromino) -> Option<Tetromino> 
 {
     let rotated = t.rotate_cw();
-    KICKS.iter().find_map(|&kick| {
+    if !t.system.can_kick(board, &rotated) {
+        return try_rotate(board, t);
+    }
+    t.system.kicks(t.kind, t.rotation_index()).iter().find_map(|&kick| {
         let kicked = Tetromino { pos:rotated.pos  + kick, ..rotated };
         if board.can_place(&kicked) {Some(kicked)} else {None}
     })
 }
 
-fn try_down(board:&Board, t:&Tetromino) -> Option<Tetromino>
+// turn without any kick
+pub(crate) fn try_rotate(board:&Board, t:&Tetromino) -> Option<Tetromino>
+{
+    let rotated = t.rotate_cw();
+    if board.can_place(&rotated) {Some(rotated)} else {None}
+}
+
+pub(crate) fn try_down(board:&Board, t:&Tetromino) -> Option<Tetromino>
 {
     let d = Pos {x : 0, y : 1};
-    let t2 = Tetromino { kind: t.kind, pos: t.pos + d, rot: t.rot };
+    let t2 = Tetromino { pos: t.pos + d, ..*t };
     if board.can_place(&t2) {Some(t2)} else {None}
 }
 
-fn try_hard_drop(board:&Board, t:&Tetromino) -> Option<Tetromino> 
+pub(crate) fn try_hard_drop(board:&Board, t:&Tetromino) -> Option<Tetromino> 
 {
-    let mut current_tetris = t.clone();
+    let mut current_tetris = *t;
     loop {
         // if let Some(next_tetris) = try_down(&board, &current_tetris) {
         //     current_tetris = next_tetris;
@@ -270,26 +388,37 @@ fn try_hard_drop(board:&Board, t:&Tetromino) -> Option<Tetromino>
         // }
 
         // or
-        let Some(next_tetris) = try_down(&board, &current_tetris) else {
+        let Some(next_tetris) = try_down(board, &current_tetris) else {
             break Some(current_tetris);
         };
         current_tetris = next_tetris;
     }
 }
 
-fn try_horizon(board:&Board, t:&Tetromino, is_left:bool) -> Option<Tetromino>
+pub(crate) fn try_horizon(board:&Board, t:&Tetromino, is_left:bool) -> Option<Tetromino>
 {
 
     let d = if is_left {Pos {x : -1, y : 0}} else {Pos {x:1, y:0}};
-    let t2 = Tetromino { kind: t.kind, pos: t.pos + d, rot: t.rot };
+    let t2 = Tetromino { pos: t.pos + d, ..*t };
     if board.can_place(&t2) {Some(t2)} else {None}
 }
 
 
-
-
-
-
+// where one press of `command` takes the piece, None if it cannot move.
+// A hard drop never fails: a piece already on the floor stays where it is (and locks).
+pub(crate) fn apply_command(board:&Board, t:&Tetromino, command:GameCommand) -> Option<Tetromino>
+{
+    use GameCommand::*;
+    match command {
+        MoveLeft =>  try_horizon(board, t, true),
+        MoveRight => try_horizon(board, t, false),
+        SoftDrop => try_down(board, t),
+        HardDrop => try_hard_drop(board, t),
+        Rotate => rotate_with_kick(board, t),
+        // holding swaps the piece, it does not move it
+        Hold | None => Option::None,
+    }
+}
 
 pub trait TetrisGenerator {
     fn next(&mut self, x: i32, y: i32) -> Tetromino;
@@ -297,17 +426,90 @@ pub trait TetrisGenerator {
 
 pub struct GameState {
     pub current_tetris: Tetromino,
+    // the current piece as it spawned, and the presses spent on it so far
+    spawned: Tetromino,
+    piece_inputs: usize,
+    // rows soft dropped since the key went down, for modes that score the push down at the lock
+    push_down: usize,
+    finesse_practice: bool,
+    // count finesse faults even without practice, for a frontend that shows them
+    track_finesse: bool,
+    // the next pieces, front first
+    queue: VecDeque<Tetromino>,
+    rules: Rules,
+    // the piece put aside with Hold, and whether the current piece already used it
+    hold: Option<TetrominoKind>,
+    hold_used: bool,
     pub shadow: Option<Tetromino>,
     pub shadow_out_of_date: bool,
     pub board: Board,
+    // left and right, see ShiftState
+    shift: ShiftState,
+    // the other commands, see debounce_index
     debounce: Vec<MotionState>,
     gravity: ConstMotion,
     lock_mgr: LockMgr,
     tetris_generator: Box<dyn TetrisGenerator>,
-    score:usize,
+    mode: Box<dyn GameMode>,
+    last_tick: Instant,
+    paused: bool,
+    stats: GameStats,
+    status: ModeStatus,
+    attack_state: AttackState,
+    // attack left after cancelling incoming garbage, since the last take_attack
+    outgoing: usize,
+    garbage: GarbageQueue,
+    // where the game is between two pieces, and when a delay phase ends
+    phase: Phase,
+    phase_end: Option<Instant>,
+    // the rows removed by the last lock, in the board as it was before
+    last_cleared: Vec<usize>,
+    entry_delay: Duration,
+    line_clear_delay: Duration,
     game_over:bool,
 }
 
+// What the game is doing. A piece falls, rests on the stack while the lock delay runs, and once it
+// locks the full rows stay for the line clear delay and the next piece waits for the entry delay
+// (ARE). Both delays are zero by default, then a lock goes straight to the next Falling piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Falling,
+    Locking,
+    ClearingLines,
+    Spawning,
+}
+
+impl Phase {
+    // a piece is in play and takes inputs
+    pub fn has_piece(&self) -> bool {
+        matches!(self, Phase::Falling | Phase::Locking)
+    }
+}
+
+// counters shared with the game mode, everything a result screen needs.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GameStats {
+    pub lines: usize,
+    pub pieces: usize,
+    pub score: usize,
+    pub level: usize,
+    // garbage lines produced by clears (before cancellation)
+    pub attack: usize,
+    // key presses beyond the optimal input, summed over all pieces (see finesse.rs)
+    pub finesse_faults: usize,
+    // active play time, driven by the `now` passed to update and frozen while paused.
+    pub elapsed: Duration,
+}
+
+impl GameStats {
+    // pieces per second
+    pub fn pps(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.pieces as f64 / secs } else { 0.0 }
+    }
+}
+
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum GameCommand {
@@ -316,125 +518,674 @@ pub enum GameCommand {
     SoftDrop,
     HardDrop,
     Rotate,
+    Hold,
     None,
 }
 
-// factory pattern
-pub fn create_new_game(width:i32, height:i32, now:Instant) -> GameState {
-    GameState::new(width, height, now, Box::new(RandomGenerator::new()))
+impl GameCommand {
+    // every command a player can press
+    pub const ALL: [GameCommand; 6] = [GameCommand::MoveLeft, GameCommand::MoveRight,
+        GameCommand::SoftDrop, GameCommand::HardDrop, GameCommand::Rotate, GameCommand::Hold];
+
+    // the order update_frame handles the commands pressed in the same frame:
+    // swap the piece first, then turn and shift it, and drop it last
+    pub const PRIORITY: [GameCommand; 6] = [GameCommand::Hold, GameCommand::Rotate, GameCommand::MoveLeft,
+        GameCommand::MoveRight, GameCommand::SoftDrop, GameCommand::HardDrop];
+
+    // snake_case name used in config files and network messages
+    pub fn name(&self) -> &'static str {
+        use GameCommand::*;
+        match self {
+            MoveLeft => "move_left",
+            MoveRight => "move_right",
+            SoftDrop => "soft_drop",
+            HardDrop => "hard_drop",
+            Rotate => "rotate",
+            Hold => "hold",
+            None => "none",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameCommand> {
+        Self::ALL.into_iter().chain([GameCommand::None]).find(|c| c.name() == name)
+    }
+}
+
+// What the player is allowed to do. Everything is on by default, presets such as the NES mode
+// (see classic.rs) turn some of it off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rules {
+    pub hold: bool,
+    pub hard_drop: bool,
+    // piece shapes and kicks, see rotation.rs
+    pub rotation: RotationSystem,
+    // how many next pieces get_queue shows
+    pub preview: usize,
+    // time between two rows while soft drop is held
+    pub soft_drop: Duration,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self { hold: true, hard_drop: true, rotation: RotationSystem::Srs, preview: NEXT_PREVIEW, soft_drop: SOFT_DROP }
+    }
+}
+
+// which commands are held down during one frame, see GameState::update_frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputState {
+    pressed: [bool; GameCommand::ALL.len()],
+}
+
+impl InputState {
+    // from the (pressed, command) pairs an InputSource gives, commands left out are released
+    pub fn from_states(states: &[(bool, GameCommand)]) -> Self {
+        let mut input = Self::default();
+        for &(down, command) in states {
+            input.set(command, down);
+        }
+        input
+    }
+
+    pub fn set(&mut self, command: GameCommand, down: bool) {
+        if let Some(i) = GameCommand::ALL.iter().position(|&c| c == command) {
+            self.pressed[i] = down;
+        }
+    }
+
+    pub fn is_pressed(&self, command: GameCommand) -> bool {
+        GameCommand::ALL.iter().position(|&c| c == command).is_some_and(|i| self.pressed[i])
+    }
+}
+
+// factory pattern, an error for a board size out of check_board_size's range
+pub fn create_new_game(width:i32, height:i32, now:Instant) -> Result<GameState, String> {
+    create_game_with_mode(width, height, now, Box::new(Endless))
+}
+
+pub fn create_game_with_mode(width:i32, height:i32, now:Instant, mode:Box<dyn GameMode>) -> Result<GameState, String> {
+    create_custom_game(width, height, now, mode, Box::new(RandomGenerator::new()))
+}
+
+// e.g. a seeded generator so both sides of a network match get the same pieces
+pub fn create_custom_game(width:i32, height:i32, now:Instant, mode:Box<dyn GameMode>,
+    generator:Box<dyn TetrisGenerator>) -> Result<GameState, String> {
+    check_board_size(width, height)?;
+    let mut game = GameState::new(width, height, now, generator);
+    game.set_mode(mode);
+    Ok(game)
 }
 
 impl GameState {
     pub fn get_score(&self) -> usize {
-        self.score
+        self.stats.score
     }
     pub fn get_game_over(&self) -> bool {
         self.game_over
     }
+    pub fn get_stats(&self) -> GameStats {
+        self.stats
+    }
+    // Playing until the mode is finished or the stack tops out (Failed).
+    pub fn get_status(&self) -> ModeStatus {
+        self.status
+    }
+    pub fn get_mode_name(&self) -> &'static str {
+        self.mode.name()
+    }
+    pub fn get_summary(&self) -> String {
+        self.mode.summary(&self.stats)
+    }
+    pub fn get_result(&self) -> GameResult {
+        GameResult { mode: self.mode.name(), status: self.status, stats: self.stats }
+    }
 
     // what is mut generator ?
     // just like let mut generator = generator// re-binding
     // make it private, use factory create_new_game instead.
-    fn new(width:i32, height:i32, now:Instant, mut generator:Box<dyn TetrisGenerator>) -> Self {
-        let current_tetris = generator.next(width/2, 1);
+    pub(crate) fn new(width:i32, height:i32, now:Instant, mut generator:Box<dyn TetrisGenerator>) -> Self {
+        let board = Board::new(width, height);
+        let spawn = board.spawn_pos();
+        let current_tetris = generator.next(spawn.x, spawn.y);
+        let queue = (0..NEXT_PREVIEW).map(|_| generator.next(spawn.x, spawn.y)).collect();
         GameState {
             current_tetris,
+            spawned: current_tetris,
+            piece_inputs: 0,
+            push_down: 0,
+            finesse_practice: false,
+            track_finesse: false,
+            queue,
+            rules: Rules::default(),
+            hold: None,
+            hold_used: false,
             shadow:None,
             shadow_out_of_date :true,
-            board: Board::new(width, height),
+            board,
             gravity: ConstMotion::new(500, now),
+            shift: ShiftState::new(120, 80),
             debounce: vec![
-                MotionState::new(120, 80),
-                MotionState::new(120, 80),
-                MotionState::new(120, 120),
+                MotionState::new(SOFT_DROP.as_millis() as u64, SOFT_DROP.as_millis() as u64),
+                MotionState::new(999999, 999999),
                 MotionState::new(999999, 999999),
                 MotionState::new(999999, 999999),
             ],
             lock_mgr: LockMgr::new(LOCK_DELAY),
             tetris_generator:generator,
-            score:0,
+            mode: Box::new(Endless),
+            last_tick: now,
+            paused: false,
+            stats: GameStats { level: 1, ..Default::default() },
+            status: ModeStatus::Playing,
+            attack_state: AttackState::default(),
+            outgoing: 0,
+            garbage: GarbageQueue::new(),
+            phase: Phase::Falling,
+            phase_end: Option::None,
+            last_cleared: Vec::new(),
+            entry_delay: Duration::ZERO,
+            line_clear_delay: Duration::ZERO,
             game_over:false,
         }
     }
 
+    fn debounce_index(command:GameCommand) -> Option<usize> {
+        use GameCommand::*;
+        match command {
+           SoftDrop => Some(0),
+           HardDrop => Some(1),
+           Rotate => Some(2),
+           Hold => Some(3),
+           // left and right go through self.shift
+           MoveLeft | MoveRight | None => Option::None,
+        }
+    }
+
     fn debounce_update(&mut self, is_press:bool, command:GameCommand, now:Instant) -> bool {
         use GameCommand::*;
         match command {
-           MoveLeft =>  self.debounce[0].update(is_press, now),
-           MoveRight => self.debounce[1].update(is_press, now),
-           SoftDrop => self.debounce[2].update(is_press, now),
-           HardDrop => self.debounce[3].update(is_press, now),
-           Rotate => self.debounce[4].update(is_press, now),
-           None => false
+            // one direction at a time, the other one keeps the state it had
+            MoveLeft => self.shift.update(is_press, self.shift.is_held(false), now) == Some(true),
+            MoveRight => self.shift.update(self.shift.is_held(true), is_press, now) == Some(false),
+            _ => Self::debounce_index(command).is_some_and(|i| self.debounce[i].update(is_press, now)),
+        }
+    }
+
+    // a fresh press of a key that moves the piece counts as one input, holding it does not add more.
+    // The hard drop is left out, the finesse search does not count it either, and a hold starts another piece.
+    fn count_input(&mut self, is_press:bool, command:GameCommand) {
+        if !is_press || command == GameCommand::HardDrop || command == GameCommand::Hold {
+            return;
+        }
+        let fresh = match command {
+            GameCommand::MoveLeft => !self.shift.is_held(true),
+            GameCommand::MoveRight => !self.shift.is_held(false),
+            _ => Self::debounce_index(command).is_some_and(|i| self.debounce[i].is_released()),
+        };
+        if fresh {
+            self.piece_inputs += 1;
         }
     }
 
 
-    fn is_game_over(&mut self) -> bool {
-        if self.board.try_place(&self.current_tetris) {
-            self.score += self.board.check_clear();
-            self.current_tetris = self.tetris_generator.next(self.board.width/2, 1);
-            self.shadow_out_of_date = true;
-            false
-        } else {
-            true
+    // lock the current piece, returns true if the game is over.
+    // With a line clear delay the full rows stay on the board until finish_lock.
+    fn lock_piece(&mut self, now:Instant) -> bool {
+        // the finesse search is a path search per lock, skipped when nobody looks at the faults
+        if self.counts_finesse() {
+            let faults = finesse::faults(&self.board, &self.spawned, &self.current_tetris, self.piece_inputs);
+            self.stats.finesse_faults += faults;
+            if faults > 0 && self.finesse_practice {
+                // practice: the piece goes back to the top instead of locking
+                self.start_piece(self.spawned.kind);
+                return false;
+            }
         }
+        if !self.board.try_place(&self.current_tetris) {
+            self.status = ModeStatus::Failed;
+            return true;
+        }
+        self.stats.score += self.mode.push_down_score(self.push_down);
+        if !self.line_clear_delay.is_zero() && !self.board.full_rows().is_empty() {
+            self.start_delay(Phase::ClearingLines, self.line_clear_delay, now);
+            return false;
+        }
+        self.finish_lock(now)
     }
-    pub fn update(&mut self, press:bool, command:GameCommand, now:Instant) -> bool
-    {
-        if self.game_over {
+
+    // clear the rows, score, garbage and the mode, then on to the next piece
+    fn finish_lock(&mut self, now:Instant) -> bool {
+        self.last_cleared = self.board.check_clear();
+        let lines = self.last_cleared.len();
+        self.stats.score += self.mode.line_score(lines, self.stats.level);
+        let perfect_clear = lines > 0 && self.board.cells.iter().all(|cell| cell.is_empty());
+        let attack = self.attack_state.on_lock(lines, perfect_clear);
+        self.stats.attack += attack;
+        // attacks cancel incoming garbage first, only the rest is sent
+        self.outgoing += self.garbage.cancel(attack);
+        if lines == 0 && !self.insert_pending_garbage() {
+            self.status = ModeStatus::Failed;
+            return true;
+        }
+        self.stats.lines += lines;
+        self.update_level();
+        self.stats.pieces += 1;
+        if !self.mode.on_lock(&mut self.board, lines) {
+            self.status = ModeStatus::Failed;
+            return true;
+        }
+        if self.check_mode() {
+            return true;
+        }
+        if self.entry_delay.is_zero() {
+            return self.spawn_next(now);
+        }
+        self.start_delay(Phase::Spawning, self.entry_delay, now);
+        false
+    }
+
+    // returns true if the game is over: the new piece has no room (block out)
+    fn spawn_next(&mut self, now:Instant) -> bool {
+        let next = self.next_piece();
+        self.start_piece(next.kind);
+        self.hold_used = false;
+        self.phase = Phase::Falling;
+        self.phase_end = Option::None;
+        // a piece that waited for ARE does not drop a row on its first frame
+        if !self.entry_delay.is_zero() {
+            self.gravity.reset(now);
+        }
+        self.check_block_out()
+    }
+
+    // garbage or the stack may have filled the spawn: the piece cannot be played, the game is lost
+    fn check_block_out(&mut self) -> bool {
+        if !self.board.can_place(&self.current_tetris) {
+            self.status = ModeStatus::Failed;
+            self.game_over = true;
+        }
+        self.game_over
+    }
+
+    fn start_delay(&mut self, phase:Phase, delay:Duration, now:Instant) {
+        self.phase = phase;
+        self.phase_end = Some(now + delay);
+    }
+
+    // end a delay phase that is over, returns false while still waiting
+    fn advance_phase(&mut self, now:Instant) -> bool {
+        let Some(end) = self.phase_end else {
+            return true;
+        };
+        if now < end {
+            return false;
+        }
+        match self.phase {
+            Phase::ClearingLines => self.game_over = self.finish_lock(end),
+            _ => self.game_over = self.spawn_next(end),
+        }
+        // a line clear followed by ARE waits again
+        !self.game_over && self.advance_phase(now)
+    }
+
+    pub fn get_phase(&self) -> Phase {
+        self.phase
+    }
+
+    // during a line clear delay: the full rows about to go and how far the delay is (0 to 1),
+    // measured at the last frame so it stands still while paused
+    pub fn get_clearing(&self) -> Option<(Vec<usize>, f64)> {
+        let end = self.phase_end.filter(|_| self.phase == Phase::ClearingLines)?;
+        let left = end.saturating_duration_since(self.last_tick).as_secs_f64();
+        let progress = 1.0 - left / self.line_clear_delay.as_secs_f64();
+        Some((self.board.full_rows(), progress.clamp(0.0, 1.0)))
+    }
+
+    // the rows the last lock cleared, empty if it cleared none
+    pub fn get_last_cleared(&self) -> &[usize] {
+        &self.last_cleared
+    }
+
+    // entry delay (ARE): the wait between a lock (or the end of its line clear) and the next piece.
+    // Keys held meanwhile still charge DAS.
+    pub fn set_entry_delay(&mut self, delay:Duration) {
+        self.entry_delay = delay;
+    }
+
+    // how long full rows stay on the board before they are removed, for the clear animation
+    pub fn set_line_clear_delay(&mut self, delay:Duration) {
+        self.line_clear_delay = delay;
+    }
+
+    // advance the play clock; only called while not paused.
+    fn tick(&mut self, now:Instant) {
+        self.stats.elapsed += now.saturating_duration_since(self.last_tick);
+        self.last_tick = now;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self, now:Instant) {
+        if self.paused || self.game_over {
+            return;
+        }
+        self.tick(now);
+        self.paused = true;
+    }
+
+    // the time spent paused is not counted, and the piece gets a fresh gravity timer.
+    pub fn resume(&mut self, now:Instant) {
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        // a delay phase and the lock delay pick up where they were paused, pausing
+        // over and over does not keep a grounded piece from locking
+        let paused_for = now.saturating_duration_since(self.last_tick);
+        if let Some(end) = self.phase_end {
+            self.phase_end = Some(end + paused_for);
+        }
+        self.lock_mgr.delay_by(paused_for);
+        self.last_tick = now;
+        self.gravity.reset(now);
+    }
+
+    fn set_mode(&mut self, mode:Box<dyn GameMode>) {
+        self.mode = mode;
+        self.mode.setup(&mut self.board);
+        self.stats.level = self.mode.start_level();
+        self.apply_gravity();
+    }
+
+    fn apply_gravity(&mut self) {
+        if let Some(delay) = self.mode.gravity(self.stats.level) {
+            self.gravity.set_delay(delay);
+        }
+    }
+
+    // level goes up every LINES_PER_LEVEL lines counted from the mode's start level.
+    fn update_level(&mut self) {
+        let level = self.mode.start_level() + self.stats.lines / LINES_PER_LEVEL;
+        if level != self.stats.level {
+            self.stats.level = level;
+            self.apply_gravity();
+        }
+    }
+
+    // a piece of `kind` where it spawns with the current rotation system
+    fn spawn_piece(&self, kind:TetrominoKind) -> Tetromino {
+        let system = self.rules.rotation;
+        Tetromino::new(kind, self.board.spawn_pos() + system.spawn_offset(kind)).with_rotation_system(system)
+    }
+
+    fn start_piece(&mut self, kind:TetrominoKind) {
+        let piece = self.spawn_piece(kind);
+        self.shift.on_spawn();
+        self.current_tetris = piece;
+        self.spawned = piece;
+        self.piece_inputs = 0;
+        self.push_down = 0;
+        self.shadow_out_of_date = true;
+    }
+
+    // finesse practice: a piece placed with more presses than needed is put back at the top
+    pub fn set_finesse_practice(&mut self, on:bool) {
+        self.finesse_practice = on;
+    }
+
+    // count finesse faults in the stats without practice, off by default
+    pub fn set_track_finesse(&mut self, on:bool) {
+        self.track_finesse = on;
+    }
+
+    // whether stats.finesse_faults is counted at all
+    pub fn counts_finesse(&self) -> bool {
+        self.finesse_practice || self.track_finesse
+    }
+
+    pub fn get_rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn set_rules(&mut self, rules:Rules) {
+        self.rules = rules;
+        self.debounce[0].set_timing(rules.soft_drop, rules.soft_drop);
+        // pieces already dealt start over with the new shapes and spawn spots, meant for right
+        // after the game is created
+        self.current_tetris = self.spawn_piece(self.current_tetris.kind);
+        self.spawned = self.current_tetris;
+        let queue: VecDeque<Tetromino> = self.queue.iter().map(|t| self.spawn_piece(t.kind)).collect();
+        self.queue = queue;
+        self.shadow_out_of_date = true;
+    }
+
+    // how long left/right is held before it repeats, and how fast it repeats then
+    pub fn set_das(&mut self, das:Duration, arr:Duration) {
+        self.shift.set_timing(das, arr);
+    }
+
+    // which of left and right moves the piece while both are held
+    pub fn set_shift_priority(&mut self, priority:ShiftPriority) {
+        self.shift.set_priority(priority);
+    }
+
+    // keep the DAS charge of a held left/right key for the next piece (the default), or make it
+    // start over with a tap
+    pub fn set_keep_das_charge(&mut self, keep:bool) {
+        self.shift.set_keep_charge(keep);
+    }
+
+    // take the next piece from the preview and refill it from the generator
+    fn next_piece(&mut self) -> Tetromino {
+        let spawn = self.board.spawn_pos();
+        let next = self.tetris_generator.next(spawn.x, spawn.y);
+        self.queue.push_back(self.spawn_piece(next.kind));
+        self.queue.pop_front().expect("preview queue is never empty")
+    }
+
+    // what Hold would bring in: the held piece, or the next one when nothing is held yet.
+    // None if the current piece already used its hold.
+    pub fn hold_swap(&self) -> Option<Tetromino> {
+        if self.hold_used || !self.rules.hold {
+            return None;
+        }
+        let kind = self.hold.or(self.queue.front().map(|t| t.kind))?;
+        Some(self.spawn_piece(kind))
+    }
+
+    pub fn get_hold(&self) -> Option<TetrominoKind> {
+        self.hold
+    }
+
+    // put the current piece aside and start the swapped one at the top, once per piece
+    fn hold_piece(&mut self, now:Instant) -> bool {
+        let Some(swap) = self.hold_swap() else {
+            return false;
+        };
+        if self.hold.is_none() {
+            self.next_piece();
+        }
+        self.hold = Some(self.current_tetris.kind);
+        self.hold_used = true;
+        self.start_piece(swap.kind);
+        self.gravity.reset(now);
+        self.lock_mgr.reset();
+        // holding into a filled spawn is a block out like any other spawn
+        self.check_block_out();
+        true
+    }
+
+    // upcoming pieces, next one first
+    pub fn get_queue(&self) -> Vec<Tetromino> {
+        self.queue.iter().take(self.rules.preview).copied().collect()
+    }
+
+    // garbage lines this player wants to send since the last call
+    pub fn take_attack(&mut self) -> usize {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    pub fn get_attack_state(&self) -> AttackState {
+        self.attack_state
+    }
+
+    // queue garbage sent by an opponent (or a trainer). It is cancelled by this player's
+    // attacks and rises from the bottom when a piece locks without clearing a line.
+    pub fn receive_garbage(&mut self, lines:usize, hole_column:i32) {
+        self.garbage.push(lines, hole_column.clamp(0, self.board.width - 1));
+    }
+
+    // lines of garbage waiting to be inserted, for the meter next to the board
+    pub fn pending_garbage(&self) -> usize {
+        self.garbage.total()
+    }
+
+    // returns false if blocks were pushed out of the top
+    fn insert_pending_garbage(&mut self) -> bool {
+        let mut fits = true;
+        for (lines, hole) in self.garbage.take_all() {
+            for _ in 0..lines {
+                fits &= self.board.push_garbage_row(hole);
+            }
+        }
+        fits
+    }
+
+    // ask the mode whether the game should end, returns true if it does.
+    fn check_mode(&mut self) -> bool {
+        self.status = self.mode.check(&self.stats);
+        self.status != ModeStatus::Playing
+    }
+
+    // tick the clock, ask the mode and end a delay that is over.
+    // false if there is nothing to play this frame.
+    fn begin_frame(&mut self, now:Instant) -> bool {
+        if self.game_over || self.paused {
+            return false;
+        }
+        self.tick(now);
+        if self.check_mode() {
+            self.game_over = true;
             return false;
         }
+        self.advance_phase(now);
+        !self.game_over
+    }
 
+    // one command, without gravity. Returns (the piece moved, it was hard dropped and locked).
+    fn apply_input(&mut self, press:bool, command:GameCommand, now:Instant) -> (bool, bool) {
+        self.count_input(press, command);
+        if command == GameCommand::SoftDrop && !press {
+            self.push_down = 0;
+        }
         let can_acntion = self.debounce_update(press, command, now);
+        self.act(can_acntion, command, now)
+    }
 
+    // carry out a command that got through its debounce
+    fn act(&mut self, can_acntion:bool, command:GameCommand, now:Instant) -> (bool, bool) {
         use GameCommand::*;
-        let moved_tetromino = 
-            if can_acntion {
-                match command {
-                    MoveLeft =>  try_horizon(&self.board, &self.current_tetris, true),
-                    MoveRight => try_horizon(&self.board, &self.current_tetris, false),
-                    SoftDrop => try_down(&self.board, &self.current_tetris),
-                    HardDrop => try_hard_drop(&self.board, &self.current_tetris),
-                    Rotate => rotate_with_kick(&self.board, &self.current_tetris),
-                    None => Option::None,
-                }
-            } else {
-                Option::None
-            };
+        // between two pieces the keys only charge up
+        if !self.phase.has_piece() {
+            return (false, false);
+        }
+        if command == Hold {
+            return (can_acntion && self.hold_piece(now), false);
+        }
+        let moved_tetromino = match command {
+            _ if !can_acntion => Option::None,
+            HardDrop if !self.rules.hard_drop => Option::None,
+            _ => apply_command(&self.board, &self.current_tetris, command),
+        };
 
-        let mut res  = false;
-        if let Some(next_pos) = moved_tetromino {
-            self.current_tetris = next_pos;
-            self.shadow_out_of_date = true;
-            res = true;
+        let Some(next_pos) = moved_tetromino else {
+            return (false, false);
+        };
+        // the mode may score the rows dropped
+        let dropped = (next_pos.pos.y - self.current_tetris.pos.y).max(0) as usize;
+        match command {
+            SoftDrop => {
+                self.stats.score += self.mode.drop_score(dropped, false);
+                self.push_down += dropped;
+            }
+            HardDrop => self.stats.score += self.mode.drop_score(dropped, true),
+            _ => {}
         }
+        self.current_tetris = next_pos;
+        self.shadow_out_of_date = true;
 
         // hard drop
-        if(GameCommand::HardDrop == command) && res {
-            self.game_over =  self.is_game_over();
+        if command == HardDrop {
+            self.game_over =  self.lock_piece(now);
             self.lock_mgr.reset();
-            return true; 
+            return (true, true);
         }
+        (true, false)
+    }
 
+    // gravity and lock delay, once a frame
+    fn fall(&mut self, now:Instant) {
         // check lock if we cannot move down
         let Some(gravity_move) = try_down(&self.board, &self.current_tetris) else {
+            self.phase = Phase::Locking;
             self.lock_mgr.start_if_not(now);
             if self.lock_mgr.lock(now) {
                 self.lock_mgr.reset();
-                self.game_over =  self.is_game_over();
+                self.game_over =  self.lock_piece(now);
             }
-            return res;
+            return;
         };
         // unlock the locking time if we can move down
+        self.phase = Phase::Falling;
         self.lock_mgr.reset();
         // check gravity timer
         if self.gravity.update(now) {
             self.current_tetris = gravity_move;
         }
+    }
+
+    // one command and a gravity step, returns true if the command did something
+    pub fn update(&mut self, press:bool, command:GameCommand, now:Instant) -> bool
+    {
+        if !self.begin_frame(now) {
+            return false;
+        }
+        let (res, locked) = self.apply_input(press, command, now);
+        if !locked && self.phase.has_piece() {
+            self.fall(now);
+        }
         res
     }
 
+    // a whole frame: every command of `input` in GameCommand::PRIORITY order, then gravity and
+    // lock delay exactly once. A hard drop ends the frame, the next piece starts falling next frame.
+    pub fn update_frame(&mut self, input:&InputState, now:Instant) {
+        if !self.begin_frame(now) {
+            return;
+        }
+        // left and right are settled together, so holding both does not depend on which comes first
+        use GameCommand::*;
+        let (left, right) = (input.is_pressed(MoveLeft), input.is_pressed(MoveRight));
+        self.count_input(left, MoveLeft);
+        self.count_input(right, MoveRight);
+        let shift = self.shift.update(left, right, now);
+        for command in GameCommand::PRIORITY {
+            let (_, locked) = match command {
+                MoveLeft | MoveRight => self.act(shift == Some(command == MoveLeft), command, now),
+                _ => self.apply_input(input.is_pressed(command), command, now),
+            };
+            if locked || self.game_over {
+                return;
+            }
+        }
+        if self.phase.has_piece() {
+            self.fall(now);
+        }
+    }
+
+    #[cfg(test)]
     fn update_press(&mut self,  command:GameCommand, now:Instant) -> bool
     {
         self.update(true, command, now)
@@ -460,6 +1211,7 @@ impl GameState {
 
 
 use rand::prelude::*;
+use rand::rngs::StdRng;
 
 // 1. 定義 struct，讓它持有泛型 R
 pub struct RandomGenerator<R: Rng> {
@@ -492,6 +1244,21 @@ impl RandomGenerator<ThreadRng> {
     }
 }
 
+// same seed, same piece sequence
+impl RandomGenerator<StdRng> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomGenerator<ThreadRng> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 
 
 #[cfg(test)]
@@ -499,34 +1266,70 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
-    struct MockGen {
-    }
-    impl TetrisGenerator for MockGen {
-        fn next(&mut self, x:i32, y:i32) -> Tetromino {
-            Tetromino { kind: TetrominoKind::I, pos:Pos{x,y}, rot: Rotation::R0 }
-        }
-    }
-    impl MockGen {
-        fn new() -> Self {
-            Self{}
-        }
+    use crate::randomizer::SequenceGenerator;
+
+    // nothing but I pieces
+    fn only_i() -> Box<dyn TetrisGenerator> {
+        Box::new(SequenceGenerator::parse("I").unwrap())
     }
 
     #[test]
     fn test_game_init() {
         // let generator = Box::new(RandomGenerator::new());
         // let game = GameState::new_game(10, 10, Instant::now(), generator);
-        let game = create_new_game(10, 10, Instant::now());
+        let game = create_new_game(10, 10, Instant::now()).unwrap();
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
         assert_eq!(game.board.width, 10);
         assert_eq!(game.board.height, 10);
-        assert_eq!(game.board.cells, vec![None; 100]);
+        assert_eq!(game.board.cells, vec![Cell::Empty; 100]);
+    }
+
+    #[test]
+    fn test_rotate_in_place() {
+        let t = Tetromino::new(TetrominoKind::T, Pos{x:5, y:5});
+        assert_eq!(t.rotate_cw().rotate_ccw(), t);
+        assert_eq!(t.rotate_ccw(), t.rotate_cw().rotate_cw().rotate_cw());
+        assert_eq!(t.rotate_ccw().rotation_index(), 3);
+        assert_eq!(t.rotate_ccw().pos, t.pos);
+    }
+
+    #[test]
+    fn test_board_from_rows() {
+        let board = Board::from_rows(&["..W.", "IGG."]).unwrap();
+        assert_eq!(board.width, 4);
+        assert_eq!(board.height, 2);
+        assert_eq!(board.get_cell(Pos{x:2, y:0}), Some(Cell::Wall));
+        assert_eq!(board.get_cell(Pos{x:0, y:1}), Some(Cell::Piece(TetrominoKind::I)));
+        assert_eq!(board.get_cell(Pos{x:4, y:1}), None);
+        assert!(board.is_occupied(Pos{x:1, y:1}));
+        assert!(!board.is_occupied(Pos{x:3, y:1}));
+        assert!(Board::from_rows(&["..", "..."]).is_none());
+        assert!(Board::from_rows(&["..?"]).is_none());
+    }
+
+    #[test]
+    fn test_wall_rows_never_clear() {
+        let mut board = Board::from_rows(&["....", "GGGG", "WGGG", "IIII"]).unwrap();
+        assert_eq!(board.check_clear(), vec![1, 3]);
+        assert_eq!(board.cells, Board::from_rows(&["....", "....", "....", "WGGG"]).unwrap().cells);
+        assert_eq!(Cell::Wall.color(), Some(SLATE));
+        assert_ne!(Cell::Garbage.color(), Cell::Wall.color());
+        assert_eq!(Cell::Empty.color(), None);
+    }
+
+    #[test]
+    fn test_seeded_generator() {
+        let mut a = RandomGenerator::with_seed(42);
+        let mut b = RandomGenerator::with_seed(42);
+        for _ in 0..20 {
+            assert_eq!(a.next(5, 1).kind, b.next(5, 1).kind);
+        }
     }
 
     #[test]
     fn test_tetris_move() {
         let init_time = Instant::now();
-        let mut game = create_new_game(10, 10, init_time);
+        let mut game = create_new_game(10, 10, init_time).unwrap();
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
         game.update_press(GameCommand::MoveLeft, init_time);
         assert_eq!(game.current_tetris.pos, Pos{x:4, y:1});
@@ -543,7 +1346,7 @@ mod tests {
     #[test]
     fn test_tetris_gravity() {
         let init_time = Instant::now();
-        let mut game = create_new_game(10, 10, init_time);
+        let mut game = create_new_game(10, 10, init_time).unwrap();
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
         game.update_press(GameCommand::None, init_time + Duration::from_millis(500));
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
@@ -556,19 +1359,19 @@ mod tests {
     #[test]
     fn test_hard_drop() {
         let init_time = Instant::now();
-        let mut game = GameState::new(10, 10, init_time, Box::new(MockGen::new()));
+        let mut game = GameState::new(10, 10, init_time, only_i());
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
         game.update_press(GameCommand::HardDrop, init_time);
-        assert_ne!(game.board.cells[9*10+4], None);
-        assert_ne!(game.board.cells[9*10+5], None);
-        assert_ne!(game.board.cells[9*10+6], None);
-        assert_ne!(game.board.cells[9*10+7], None);
+        assert_ne!(game.board.cells[9*10+4], Cell::Empty);
+        assert_ne!(game.board.cells[9*10+5], Cell::Empty);
+        assert_ne!(game.board.cells[9*10+6], Cell::Empty);
+        assert_ne!(game.board.cells[9*10+7], Cell::Empty);
     }
 
     #[test]
     fn test_soft_drop() {
         let init_time = Instant::now();
-        let mut game = create_new_game(10, 10, init_time);
+        let mut game = create_new_game(10, 10, init_time).unwrap();
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
         game.update_press(GameCommand::SoftDrop, init_time);
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:2});
@@ -581,7 +1384,7 @@ mod tests {
     #[test]
     fn test_hard_drop_debounce() {
         let init_time = Instant::now();
-        let mut game = GameState::new(10, 10, init_time, Box::new(MockGen::new()));
+        let mut game = GameState::new(10, 10, init_time, only_i());
         game.update_press(GameCommand::SoftDrop, init_time);
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:2});
 
@@ -604,7 +1407,7 @@ mod tests {
     #[test]
     fn test_lock() {
         let init_time = Instant::now();
-        let mut game = GameState::new(10, 3, init_time, Box::new(MockGen::new()));
+        let mut game = GameState::new(10, 3, init_time, only_i());
         assert!(game.update_press(GameCommand::SoftDrop, init_time));
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:2});
 
@@ -614,10 +1417,10 @@ mod tests {
 
         // lock!
         assert!(!game.update_press(GameCommand::None, init_time + Duration::from_millis(501)));
-        assert_ne!(game.board.cells[2*10+3], None);
-        assert_ne!(game.board.cells[2*10+4], None);
-        assert_ne!(game.board.cells[2*10+5], None);
-        assert_ne!(game.board.cells[2*10+6], None);
+        assert_ne!(game.board.cells[2*10+3], Cell::Empty);
+        assert_ne!(game.board.cells[2*10+4], Cell::Empty);
+        assert_ne!(game.board.cells[2*10+5], Cell::Empty);
+        assert_ne!(game.board.cells[2*10+6], Cell::Empty);
         // new
         assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
     }
@@ -625,11 +1428,321 @@ mod tests {
     #[test]
     fn test_bug_hard_drop_instant_game_over() {
         let init_time = Instant::now();
-        let mut game = GameState::new(10, 5, init_time, Box::new(MockGen::new()));
+        let mut game = GameState::new(10, 5, init_time, only_i());
         game.update(true, GameCommand::HardDrop, init_time);
         game.update(true, GameCommand::HardDrop, init_time);
         game.update(true, GameCommand::HardDrop, init_time);
         assert!(!game.get_game_over());
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_sprint_finish() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_mode(Box::new(crate::mode::Sprint::new(1)));
+        // leave a hole for the I piece at x 4..=7 on the bottom row
+        for x in [0, 1, 2, 3, 8, 9] {
+            game.board.cells[9*10+x] = Cell::Piece(TetrominoKind::O);
+        }
+        assert!(game.update_press(GameCommand::HardDrop, init_time + Duration::from_millis(1500)));
+        assert!(game.get_game_over());
+        assert_eq!(game.get_status(), ModeStatus::Finished);
+        let stats = game.get_stats();
+        assert_eq!(stats.lines, 1);
+        assert_eq!(stats.pieces, 1);
+        assert_eq!(stats.elapsed, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_pause_freezes_clock() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_mode(Box::new(crate::mode::Ultra::new(Duration::from_secs(2))));
+        game.update_press(GameCommand::None, init_time + Duration::from_millis(1000));
+        game.pause(init_time + Duration::from_millis(1200));
+        // nothing moves while paused
+        assert!(!game.update_press(GameCommand::MoveLeft, init_time + Duration::from_millis(5000)));
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:2});
+        game.resume(init_time + Duration::from_millis(5000));
+        game.update_press(GameCommand::None, init_time + Duration::from_millis(5500));
+        assert_eq!(game.get_stats().elapsed, Duration::from_millis(1700));
+        assert!(!game.get_game_over());
+        // gravity restarted on resume instead of catching up
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:2});
+
+        game.update_press(GameCommand::None, init_time + Duration::from_millis(5800));
+        assert!(game.get_game_over());
+        assert_eq!(game.get_status(), ModeStatus::Finished);
+    }
+
+    #[test]
+    fn test_pause_keeps_lock_delay() {
+        let init_time = Instant::now();
+        let at = |ms| init_time + Duration::from_millis(ms);
+        let mut game = GameState::new(10, 3, init_time, only_i());
+        assert!(game.update_press(GameCommand::SoftDrop, init_time));
+        // grounded, then 100ms of play and 900ms of pause, three times
+        for i in 0..3 {
+            game.pause(at(i * 1000 + 100));
+            game.resume(at(i * 1000 + 1000));
+        }
+        // 300ms of the 500 are used up before the last resume
+        game.update_press(GameCommand::None, at(3200));
+        assert_eq!(game.get_stats().pieces, 0);
+        game.update_press(GameCommand::None, at(3201));
+        assert_eq!(game.get_stats().pieces, 1);
+    }
+
+    #[test]
+    fn test_line_clear_score() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_mode(Box::new(crate::mode::Ultra::default()));
+        for x in [0, 1, 2, 3, 8, 9] {
+            game.board.cells[9*10+x] = Cell::Piece(TetrominoKind::O);
+        }
+        game.update_press(GameCommand::HardDrop, init_time);
+        // 8 cells of hard drop + single
+        assert_eq!(game.get_score(), 2 * 8 + 100);
+    }
+
+    #[test]
+    fn test_marathon_level_up() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_mode(Box::new(crate::mode::Marathon::new(12, 1)));
+        assert_eq!(game.get_stats().level, 1);
+        game.stats.lines = 9;
+        for x in [0, 1, 2, 3, 8, 9] {
+            game.board.cells[9*10+x] = Cell::Piece(TetrominoKind::O);
+        }
+        game.update_press(GameCommand::HardDrop, init_time);
+        let stats = game.get_stats();
+        assert_eq!(stats.lines, 10);
+        assert_eq!(stats.level, 2);
+        assert_eq!(game.get_status(), ModeStatus::Playing);
+
+        // the new piece falls at the level 2 speed (0.793s per row)
+        game.update(false, GameCommand::HardDrop, init_time + Duration::from_millis(790));
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
+        game.update(false, GameCommand::HardDrop, init_time + Duration::from_millis(800));
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:2});
+    }
+
+    #[test]
+    fn test_receive_garbage() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.receive_garbage(2, 3);
+        game.receive_garbage(1, 42);
+        assert_eq!(game.pending_garbage(), 3);
+
+        // a lock without clearing lets it in
+        game.update_press(GameCommand::HardDrop, init_time);
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.board.garbage_rows(), 3);
+        // oldest garbage ends up on top, the column is clamped to the board
+        assert_eq!(game.board.get_cell(Pos{x:3, y:7}), Some(Cell::Empty));
+        assert_eq!(game.board.get_cell(Pos{x:3, y:8}), Some(Cell::Empty));
+        assert_eq!(game.board.get_cell(Pos{x:9, y:9}), Some(Cell::Empty));
+        // the I piece was lifted with the stack
+        assert_eq!(game.board.get_cell(Pos{x:4, y:6}), Some(Cell::Piece(TetrominoKind::I)));
+        assert!(!game.get_game_over());
+    }
+
+    #[test]
+    fn test_attack_cancels_garbage() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        // vertical I into column 6 makes a tetris, one extra block avoids a perfect clear
+        game.board.cells[5*10] = Cell::Garbage;
+        for y in 6..10 {
+            for x in (0..10).filter(|&x| x != 6) {
+                game.board.cells[y*10+x] = Cell::Garbage;
+            }
+        }
+        game.receive_garbage(3, 0);
+        game.update_press(GameCommand::Rotate, init_time);
+        game.update_press(GameCommand::HardDrop, init_time);
+        assert_eq!(game.get_stats().lines, 4);
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.take_attack(), 1);
+        assert_eq!(game.take_attack(), 0);
+    }
+
+    #[test]
+    fn test_board_size() {
+        assert!(check_board_size(10, 20).is_ok());
+        assert!(check_board_size(MIN_BOARD_WIDTH, MIN_BOARD_HEIGHT).is_ok());
+        assert!(check_board_size(4, 20).is_err());
+        assert!(check_board_size(10, 0).is_err());
+        assert!(check_board_size(-10, 20).is_err());
+        assert!(check_board_size(10, MAX_BOARD_SIZE + 1).is_err());
+        // the factories refuse them too
+        assert!(create_new_game(10, 20, Instant::now()).is_ok());
+        assert!(create_new_game(100_000, 100_000, Instant::now()).is_err());
+
+        // the narrowest board still spawns every piece inside it
+        use TetrominoKind::*;
+        let board = Board::new(MIN_BOARD_WIDTH, MIN_BOARD_HEIGHT);
+        for kind in [I, O, T, J, L, S, Z] {
+            assert!(board.can_place(&Tetromino::new(kind, board.spawn_pos())));
+        }
+    }
+
+    #[test]
+    fn test_finesse_faults() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_finesse_practice(true);
+        // tapping to the wall takes 4 presses, holding the key takes one
+        for _ in 0..4 {
+            game.update_press(GameCommand::MoveLeft, init_time);
+            game.update(false, GameCommand::MoveLeft, init_time);
+        }
+        game.update_press(GameCommand::HardDrop, init_time);
+        assert_eq!(game.get_stats().finesse_faults, 3);
+        // practice mode put the piece back instead of locking it
+        assert_eq!(game.get_stats().pieces, 0);
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
+        game.update(false, GameCommand::HardDrop, init_time);
+
+        for ms in [0, 130, 220, 310, 400] {
+            game.update_press(GameCommand::MoveLeft, init_time + Duration::from_millis(ms));
+        }
+        game.update_press(GameCommand::HardDrop, init_time + Duration::from_millis(400));
+        assert_eq!(game.get_stats().finesse_faults, 3);
+        assert_eq!(game.get_stats().pieces, 1);
+    }
+
+    #[test]
+    fn test_finesse_tracking() {
+        let init_time = Instant::now();
+        let tap_to_wall = |game:&mut GameState| {
+            for _ in 0..4 {
+                game.update_press(GameCommand::MoveLeft, init_time);
+                game.update(false, GameCommand::MoveLeft, init_time);
+            }
+            game.update_press(GameCommand::HardDrop, init_time);
+            game.update(false, GameCommand::HardDrop, init_time);
+        };
+        // not counted unless asked for
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        tap_to_wall(&mut game);
+        assert_eq!(game.get_stats().finesse_faults, 0);
+        // counted, but the piece locks
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_track_finesse(true);
+        tap_to_wall(&mut game);
+        assert_eq!(game.get_stats().finesse_faults, 3);
+        assert_eq!(game.get_stats().pieces, 1);
+    }
+
+    #[test]
+    fn test_hold() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, Box::new(RandomGenerator::with_seed(3)));
+        let first = game.current_tetris.kind;
+        let next = game.get_queue()[0].kind;
+        game.current_tetris.pos.x = 2;
+        // nothing held yet: the next piece comes in, at the spawn
+        assert!(game.update_press(GameCommand::Hold, init_time));
+        assert_eq!(game.get_hold(), Some(first));
+        assert_eq!(game.current_tetris, Tetromino::new(next, game.board.spawn_pos()));
+        // once per piece
+        game.update(false, GameCommand::Hold, init_time);
+        assert!(!game.update_press(GameCommand::Hold, init_time));
+        assert_eq!(game.hold_swap(), Option::None);
+        game.update(false, GameCommand::Hold, init_time);
+
+        game.update_press(GameCommand::HardDrop, init_time);
+        let third = game.current_tetris.kind;
+        assert!(game.update_press(GameCommand::Hold, init_time));
+        assert_eq!(game.get_hold(), Some(third));
+        assert_eq!(game.current_tetris.kind, first);
+    }
+
+    #[test]
+    fn test_hold_into_blocked_spawn() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, Box::new(SequenceGenerator::parse("IO").unwrap()));
+        let mut rows = vec![".........."; 10];
+        rows[1] = "...GGGG...";
+        game.board = Board::from_rows(&rows).unwrap();
+        game.current_tetris.pos.y = 5;
+        assert!(game.update_press(GameCommand::Hold, init_time));
+        assert!(game.get_game_over());
+        assert_eq!(game.get_status(), ModeStatus::Failed);
+    }
+
+    #[test]
+    fn test_line_clear_and_entry_delay() {
+        let init_time = Instant::now();
+        let at = |ms| init_time + Duration::from_millis(ms);
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        game.set_line_clear_delay(Duration::from_millis(300));
+        game.set_entry_delay(Duration::from_millis(200));
+        let mut rows = vec![".........."; 9];
+        rows.push("IIII....II");
+        game.board = Board::from_rows(&rows).unwrap();
+
+        let drop = InputState::from_states(&[(true, GameCommand::HardDrop)]);
+        game.update_frame(&drop, init_time);
+        // the full row stays for the clear delay
+        assert_eq!(game.get_phase(), Phase::ClearingLines);
+        assert_eq!(game.board.full_rows(), vec![9]);
+        game.update_frame(&InputState::default(), at(150));
+        assert_eq!(game.get_clearing(), Some((vec![9], 0.5)));
+        game.update_frame(&InputState::default(), at(299));
+        assert_eq!(game.get_stats().lines, 0);
+        game.update_frame(&InputState::default(), at(300));
+        assert_eq!(game.get_phase(), Phase::Spawning);
+        assert_eq!(game.get_stats().lines, 1);
+        assert!(game.board.full_rows().is_empty());
+        assert_eq!(game.get_clearing(), Option::None);
+        assert_eq!(game.get_last_cleared(), &[9]);
+
+        // left held during ARE charges DAS but does not move anything
+        let left = InputState::from_states(&[(true, GameCommand::MoveLeft)]);
+        game.update_frame(&left, at(400));
+        game.update_frame(&left, at(499));
+        assert_eq!(game.get_phase(), Phase::Spawning);
+        game.update_frame(&left, at(500));
+        assert_eq!(game.get_phase(), Phase::Falling);
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
+        game.update_frame(&left, at(521));
+        assert_eq!(game.current_tetris.pos, Pos{x:4, y:1});
+    }
+
+    #[test]
+    fn test_update_frame() {
+        let init_time = Instant::now();
+        let mut game = GameState::new(10, 10, init_time, only_i());
+        let mut input = InputState::default();
+        input.set(GameCommand::Rotate, true);
+        input.set(GameCommand::MoveLeft, true);
+        // both in one frame: rotated first, then moved
+        game.update_frame(&input, init_time);
+        let mut expected = Tetromino::new(TetrominoKind::I, Pos{x:4, y:1}).rotate_cw();
+        assert_eq!(game.current_tetris, expected);
+
+        // gravity runs once per frame, not once per command
+        let input = InputState::from_states(&[(false, GameCommand::MoveLeft), (true, GameCommand::SoftDrop)]);
+        game.update_frame(&input, init_time + Duration::from_millis(501));
+        // one row for the soft drop and one for gravity (its 500ms are up)
+        expected.pos.y += 2;
+        assert_eq!(game.current_tetris, expected);
+
+        // shift before the drop: the piece lands one column further left
+        let input = InputState::from_states(&[(true, GameCommand::MoveLeft), (true, GameCommand::HardDrop)]);
+        game.update_frame(&InputState::default(), init_time + Duration::from_millis(502));
+        game.update_frame(&input, init_time + Duration::from_millis(503));
+        assert_eq!(game.get_stats().pieces, 1);
+        for y in 6..10 {
+            assert_eq!(game.board.get_cell(Pos{x:4, y}), Some(Cell::Piece(TetrominoKind::I)));
+        }
+        // the new piece did not fall in the frame of the drop
+        assert_eq!(game.current_tetris.pos, Pos{x:5, y:1});
+    }
+
+}
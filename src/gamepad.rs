@@ -0,0 +1,254 @@
+// Gamepads through the Linux joystick interface (/dev/input/js0, ...).
+// The device is read as a stream of 8 byte events:
+//
+//     u32 time (ms), i16 value, u8 type, u8 number
+//
+// type is 1 for a button (value 0 or 1) and 2 for an axis (-32767..32767), with 0x80 added for the
+// events the driver sends on open to report the initial state.
+// Sticks and d-pads are axes, each direction of an axis can be bound like a button.
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::bindings::Bindings;
+use crate::game::GameCommand;
+use crate::source::InputSource;
+
+pub const JS_EVENT_SIZE: usize = 8;
+pub const JS_EVENT_BUTTON: u8 = 0x01;
+pub const JS_EVENT_AXIS: u8 = 0x02;
+pub const JS_EVENT_INIT: u8 = 0x80;
+// how far a stick has to be pushed to count as a press
+pub const AXIS_THRESHOLD: i16 = 16384;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JsEvent {
+    pub time: u32,
+    pub value: i16,
+    pub kind: u8,
+    pub number: u8,
+}
+
+impl JsEvent {
+    // the kernel writes the struct in native byte order
+    pub fn from_bytes(b: [u8; JS_EVENT_SIZE]) -> Self {
+        Self {
+            time: u32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+            value: i16::from_ne_bytes([b[4], b[5]]),
+            kind: b[6],
+            number: b[7],
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; JS_EVENT_SIZE] {
+        let mut b = [0; JS_EVENT_SIZE];
+        b[..4].copy_from_slice(&self.time.to_ne_bytes());
+        b[4..6].copy_from_slice(&self.value.to_ne_bytes());
+        b[6] = self.kind;
+        b[7] = self.number;
+        b
+    }
+}
+
+// a button, or one direction of an axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PadInput {
+    Button(u8),
+    AxisMinus(u8),
+    AxisPlus(u8),
+}
+
+impl PadInput {
+    // names used in the bindings file: button0, axis1-, axis6+
+    pub fn from_name(name: &str) -> Option<PadInput> {
+        let name = name.to_ascii_lowercase();
+        if let Some(n) = name.strip_prefix("button") {
+            return n.parse().ok().map(PadInput::Button);
+        }
+        let axis = name.strip_prefix("axis")?;
+        if let Some(n) = axis.strip_suffix('-') {
+            return n.parse().ok().map(PadInput::AxisMinus);
+        }
+        axis.strip_suffix('+')?.parse().ok().map(PadInput::AxisPlus)
+    }
+}
+
+// left stick or d-pad (axes 6/7 on most pads) to move, down to soft drop, up to hard drop,
+// the two main face buttons rotate and the third one holds
+pub const PAD_DEFAULTS: [(PadInput, GameCommand); 11] = [
+    (PadInput::AxisMinus(0), GameCommand::MoveLeft),
+    (PadInput::AxisMinus(6), GameCommand::MoveLeft),
+    (PadInput::AxisPlus(0), GameCommand::MoveRight),
+    (PadInput::AxisPlus(6), GameCommand::MoveRight),
+    (PadInput::AxisPlus(1), GameCommand::SoftDrop),
+    (PadInput::AxisPlus(7), GameCommand::SoftDrop),
+    (PadInput::AxisMinus(1), GameCommand::HardDrop),
+    (PadInput::AxisMinus(7), GameCommand::HardDrop),
+    (PadInput::Button(0), GameCommand::Rotate),
+    (PadInput::Button(1), GameCommand::Rotate),
+    (PadInput::Button(2), GameCommand::Hold),
+];
+
+// the first /dev/input/js* device, if any
+pub fn find_device() -> Option<PathBuf> {
+    (0..16).map(|i| PathBuf::from(format!("/dev/input/js{}", i))).find(|p| p.exists())
+}
+
+// A gamepad as an InputSource. Events are read on a thread since reading the device blocks until
+// something happens; the thread ends when the device goes away.
+pub struct Gamepad {
+    events: Receiver<JsEvent>,
+    down: HashSet<PadInput>,
+    bindings: Bindings<PadInput>,
+    connected: bool,
+}
+
+impl Gamepad {
+    pub fn open(path: &Path, bindings: Bindings<PadInput>) -> io::Result<Self> {
+        Ok(Self::from_reader(File::open(path)?, bindings))
+    }
+
+    // any byte stream of js events, e.g. a file recorded from a device
+    pub fn from_reader(mut reader: impl Read + Send + 'static, bindings: Bindings<PadInput>) -> Self {
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0; JS_EVENT_SIZE];
+            while reader.read_exact(&mut buf).is_ok() {
+                if tx.send(JsEvent::from_bytes(buf)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { events, down: HashSet::new(), bindings, connected: true }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn apply(&mut self, event: JsEvent) {
+        let n = event.number;
+        match event.kind & !JS_EVENT_INIT {
+            JS_EVENT_BUTTON => {
+                self.set(PadInput::Button(n), event.value != 0);
+            }
+            JS_EVENT_AXIS => {
+                self.set(PadInput::AxisMinus(n), event.value <= -AXIS_THRESHOLD);
+                self.set(PadInput::AxisPlus(n), event.value >= AXIS_THRESHOLD);
+            }
+            _ => {}
+        }
+    }
+
+    fn set(&mut self, input: PadInput, down: bool) {
+        if down {
+            self.down.insert(input);
+        } else {
+            self.down.remove(&input);
+        }
+    }
+}
+
+impl InputSource for Gamepad {
+    fn poll(&mut self) -> Vec<(bool, GameCommand)> {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.apply(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // unplugged: let go of everything
+                    self.connected = false;
+                    self.down.clear();
+                    break;
+                }
+            }
+        }
+        self.bindings.states(|input| self.down.contains(&input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn event(kind: u8, number: u8, value: i16) -> [u8; JS_EVENT_SIZE] {
+        JsEvent { time: 0, value, kind, number }.to_bytes()
+    }
+
+    fn pressed(states: &[(bool, GameCommand)], command: GameCommand) -> bool {
+        states.contains(&(true, command))
+    }
+
+    // poll until the reader thread reached the end of the fake device
+    fn poll_all(pad: &mut Gamepad) -> Vec<(bool, GameCommand)> {
+        let mut states = pad.poll();
+        for _ in 0..200 {
+            if !pad.is_connected() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+            states = pad.poll();
+        }
+        states
+    }
+
+    #[test]
+    fn test_pad_input_names() {
+        assert_eq!(PadInput::from_name("button3"), Some(PadInput::Button(3)));
+        assert_eq!(PadInput::from_name("Axis6-"), Some(PadInput::AxisMinus(6)));
+        assert_eq!(PadInput::from_name("axis1+"), Some(PadInput::AxisPlus(1)));
+        assert_eq!(PadInput::from_name("axis1"), None);
+        assert_eq!(PadInput::from_name("trigger"), None);
+    }
+
+    #[test]
+    fn test_event_bytes() {
+        let e = JsEvent { time: 1234, value: -32767, kind: JS_EVENT_AXIS, number: 6 };
+        assert_eq!(JsEvent::from_bytes(e.to_bytes()), e);
+    }
+
+    #[test]
+    fn test_fake_device() {
+        let path = std::env::temp_dir().join(format!("tetris-fake-js-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        // initial state, then d-pad left, button 0 pressed and released, stick pushed down
+        file.write_all(&event(JS_EVENT_BUTTON | JS_EVENT_INIT, 0, 0)).unwrap();
+        file.write_all(&event(JS_EVENT_AXIS, 6, -32767)).unwrap();
+        file.write_all(&event(JS_EVENT_BUTTON, 0, 1)).unwrap();
+        file.write_all(&event(JS_EVENT_BUTTON, 0, 0)).unwrap();
+        file.write_all(&event(JS_EVENT_AXIS, 1, 20000)).unwrap();
+        file.write_all(&event(JS_EVENT_BUTTON, 2, 1)).unwrap();
+        drop(file);
+
+        let mut bindings = Bindings::new(&PAD_DEFAULTS);
+        bindings.load("hard_drop = button2", "single", PadInput::from_name).unwrap();
+        let file = File::open(&path).unwrap();
+        let mut pad = Gamepad::from_reader(file, bindings);
+        // the device "ends" here, which looks like an unplugged pad and releases everything,
+        // so look at what was pressed through the events one by one
+        let mut seen = Vec::new();
+        for _ in 0..200 {
+            if let Ok(event) = pad.events.recv_timeout(Duration::from_millis(100)) {
+                pad.apply(event);
+                seen.push(pad.bindings.states(|input| pad.down.contains(&input)));
+            } else {
+                break;
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(seen.len(), 6);
+        assert!(pressed(&seen[1], GameCommand::MoveLeft));
+        assert!(pressed(&seen[2], GameCommand::Rotate));
+        assert!(!pressed(&seen[3], GameCommand::Rotate));
+        assert!(pressed(&seen[4], GameCommand::SoftDrop) && pressed(&seen[4], GameCommand::MoveLeft));
+        assert!(pressed(&seen[5], GameCommand::HardDrop));
+        // the end of the file lets go of everything
+        assert!(poll_all(&mut pad).iter().all(|&(down, _)| !down));
+    }
+}
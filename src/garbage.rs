@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+// Picks the hole column of each garbage row.
+// messiness is the chance (0.0 ..= 1.0) that the hole moves away from the column of the
+// previous row: 0.0 gives one straight well, 1.0 a new column every row. Values out of range are
+// clamped, NaN or an infinity is refused when the generator is made rather than when a hole is picked.
+pub struct GarbageGenerator {
+    rng: StdRng,
+    messiness: f64,
+    last_hole: Option<i32>,
+}
+
+impl GarbageGenerator {
+    pub fn new(messiness: f64) -> Self {
+        Self::from_rng(StdRng::from_rng(&mut rand::rng()), messiness)
+    }
+
+    // same seed, same holes. Used by tests and anything that has to be reproducible.
+    pub fn with_seed(messiness: f64, seed: u64) -> Self {
+        Self::from_rng(StdRng::seed_from_u64(seed), messiness)
+    }
+
+    fn from_rng(rng: StdRng, messiness: f64) -> Self {
+        assert!(messiness.is_finite(), "garbage messiness has to be a number, got {}", messiness);
+        Self { rng, messiness: messiness.clamp(0.0, 1.0), last_hole: None }
+    }
+
+    pub fn next_hole(&mut self, width: i32) -> i32 {
+        let hole = match self.last_hole {
+            Some(last) if last < width && !self.rng.random_bool(self.messiness) => last,
+            Some(last) if last < width && width > 1 => {
+                // any column but the previous one
+                let hole = self.rng.random_range(0..width - 1);
+                if hole >= last { hole + 1 } else { hole }
+            }
+            _ => self.rng.random_range(0..width),
+        };
+        self.last_hole = Some(hole);
+        hole
+    }
+}
+
+// Garbage waiting to be inserted into a board, oldest attack first.
+// Each entry is (lines, hole column).
+#[derive(Default)]
+pub struct GarbageQueue {
+    pending: VecDeque<(usize, i32)>,
+}
+
+impl GarbageQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, lines: usize, hole: i32) {
+        if lines > 0 {
+            self.pending.push_back((lines, hole));
+        }
+    }
+
+    // total lines waiting
+    pub fn total(&self) -> usize {
+        self.pending.iter().map(|(lines, _)| lines).sum()
+    }
+
+    // outgoing attack cancels the oldest pending garbage first.
+    // returns what is left of the attack to send to the opponent.
+    pub fn cancel(&mut self, mut attack: usize) -> usize {
+        while attack > 0 {
+            let Some(front) = self.pending.front_mut() else {
+                break;
+            };
+            let cancelled = attack.min(front.0);
+            front.0 -= cancelled;
+            attack -= cancelled;
+            if front.0 == 0 {
+                self.pending.pop_front();
+            }
+        }
+        attack
+    }
+
+    pub fn take_all(&mut self) -> Vec<(usize, i32)> {
+        self.pending.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_cancel() {
+        let mut queue = GarbageQueue::new();
+        queue.push(2, 3);
+        queue.push(4, 7);
+        queue.push(0, 1);
+        assert_eq!(queue.total(), 6);
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.total(), 3);
+        assert_eq!(queue.cancel(5), 2);
+        assert_eq!(queue.total(), 0);
+
+        queue.push(1, 0);
+        queue.push(2, 5);
+        assert_eq!(queue.take_all(), vec![(1, 0), (2, 5)]);
+        assert_eq!(queue.total(), 0);
+    }
+
+    #[test]
+    fn test_clean_garbage_keeps_column() {
+        let mut generator = GarbageGenerator::with_seed(0.0, 7);
+        let first = generator.next_hole(10);
+        assert!((0..10).contains(&first));
+        assert!((0..20).all(|_| generator.next_hole(10) == first));
+    }
+
+    #[test]
+    fn test_messy_garbage_always_moves() {
+        let mut generator = GarbageGenerator::with_seed(1.0, 7);
+        let mut last = generator.next_hole(10);
+        for _ in 0..50 {
+            let hole = generator.next_hole(10);
+            assert!((0..10).contains(&hole));
+            assert_ne!(hole, last);
+            last = hole;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "messiness")]
+    fn test_nan_messiness() {
+        GarbageGenerator::with_seed(f64::NAN, 7);
+    }
+}
@@ -1,6 +1,7 @@
 
 
 use std::time::{Duration, Instant};
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq, Clone, Copy,Debug)]
 enum KeyState {NPRESS, PRESS, DAS}
 
@@ -29,10 +30,17 @@ impl MotionState {
     pub fn reset_last(&mut self, time:Instant) {
         self.last_time = Some(time);
     }
+    // not held down since the last update
+    pub fn is_released(&self) -> bool {
+        self.key_state == KeyState::NPRESS
+    }
     pub fn reset_all(&mut self) {
         self.key_state = KeyState::NPRESS;
         self.last_time = None;
     }
+    pub fn set_timing(&mut self, das_delay:Duration, arr:Duration) {
+        self.config = MotionConfig { das_delay, arr };
+    }
 
     pub fn update(&mut self, is_pressed:bool, current_time: Instant) -> bool
     {
@@ -73,6 +81,95 @@ impl MotionState {
     }
 }
 
+// who moves the piece while left and right are both held
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShiftPriority {
+    // the key pressed last takes over, letting go of it hands back to the other one
+    #[default]
+    LastPressed,
+    // the key pressed first keeps the piece until it is released
+    FirstPressed,
+}
+
+// Left and right together. Only one direction moves at a time, and switching direction starts the
+// new one from a fresh tap: DAS charged in one direction never carries over to the other.
+pub struct ShiftState {
+    left: MotionState,
+    right: MotionState,
+    // keys held down, oldest first, true for left
+    order: Vec<bool>,
+    active: Option<bool>,
+    priority: ShiftPriority,
+    // keep the charge of a held key when a new piece spawns
+    keep_charge: bool,
+}
+
+impl ShiftState {
+    pub fn new(das_delay:u64, arr:u64) -> Self {
+        Self {
+            left: MotionState::new(das_delay, arr),
+            right: MotionState::new(das_delay, arr),
+            order: Vec::new(),
+            active: None,
+            priority: ShiftPriority::default(),
+            keep_charge: true,
+        }
+    }
+
+    pub fn set_priority(&mut self, priority:ShiftPriority) {
+        self.priority = priority;
+    }
+
+    pub fn set_keep_charge(&mut self, keep:bool) {
+        self.keep_charge = keep;
+    }
+
+    pub fn set_timing(&mut self, das_delay:Duration, arr:Duration) {
+        self.left.set_timing(das_delay, arr);
+        self.right.set_timing(das_delay, arr);
+    }
+
+    // the key was down at the last update
+    pub fn is_held(&self, left:bool) -> bool {
+        self.order.contains(&left)
+    }
+
+    fn motion(&mut self, left:bool) -> &mut MotionState {
+        if left { &mut self.left } else { &mut self.right }
+    }
+
+    // both keys for this frame, returns the direction to move: Some(true) for left
+    pub fn update(&mut self, left_down:bool, right_down:bool, now:Instant) -> Option<bool> {
+        for (dir, down) in [(true, left_down), (false, right_down)] {
+            if !down {
+                self.order.retain(|&d| d != dir);
+            } else if !self.order.contains(&dir) {
+                self.order.push(dir);
+            }
+        }
+        let active = match self.priority {
+            ShiftPriority::LastPressed => self.order.last(),
+            ShiftPriority::FirstPressed => self.order.first(),
+        }.copied();
+        if active != self.active {
+            // direction change or release: the charge is gone
+            self.left.reset_all();
+            self.right.reset_all();
+            self.active = active;
+        }
+        let dir = active?;
+        self.motion(dir).update(true, now).then_some(dir)
+    }
+
+    // a new piece: a held key either keeps its charge (modern clients) or starts over with a tap
+    pub fn on_spawn(&mut self) {
+        if !self.keep_charge {
+            self.left.reset_all();
+            self.right.reset_all();
+        }
+    }
+}
+
 pub struct ConstMotion {
     impl_motion : MotionState,
 }
@@ -86,6 +183,9 @@ impl ConstMotion {
     pub fn reset(&mut self, time:Instant) {
         self.impl_motion.reset_last(time);
     }
+    pub fn set_delay(&mut self, delay:Duration) {
+        self.impl_motion.config = MotionConfig {das_delay: delay, arr: delay};
+    }
     pub fn update(&mut self, current_time: Instant) -> bool {
         self.impl_motion.update(true, current_time)
     }
@@ -107,6 +207,11 @@ impl LockMgr {
         self.lock_start_time = None;
     }
 
+    // a pause: the lock delay goes on where it stopped
+    pub fn delay_by(&mut self, paused:Duration) {
+        self.lock_start_time = self.lock_start_time.map(|start| start + paused);
+    }
+
     pub fn start_if_not(&mut self, now:Instant) -> Option<Instant> {
         self.lock_start_time = self.lock_start_time.or(Some(now));
         self.lock_start_time
@@ -169,6 +274,75 @@ mod tests {
 
     }
 
+    fn ms(start:Instant, ms:u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_shift_last_pressed_wins() {
+        let mut shift = ShiftState::new(100, 50);
+        let start = Instant::now();
+        assert_eq!(shift.update(true, false, start), Some(true));
+        // right pressed while left is held: right takes over with a tap
+        assert_eq!(shift.update(true, true, ms(start, 20)), Some(false));
+        // and charges on its own, left never moves meanwhile
+        assert_eq!(shift.update(true, true, ms(start, 60)), None);
+        assert_eq!(shift.update(true, true, ms(start, 121)), Some(false));
+        assert_eq!(shift.update(true, true, ms(start, 172)), Some(false));
+        // letting go of right hands back to left, from a fresh tap
+        assert_eq!(shift.update(true, false, ms(start, 180)), Some(true));
+        assert_eq!(shift.update(true, false, ms(start, 240)), None);
+        assert_eq!(shift.update(false, false, ms(start, 250)), None);
+        assert!(!shift.is_held(true));
+    }
+
+    #[test]
+    fn test_shift_first_pressed_wins() {
+        let mut shift = ShiftState::new(100, 50);
+        shift.set_priority(ShiftPriority::FirstPressed);
+        let start = Instant::now();
+        assert_eq!(shift.update(true, false, start), Some(true));
+        assert_eq!(shift.update(true, true, ms(start, 20)), None);
+        // left still charges up while right is ignored
+        assert_eq!(shift.update(true, true, ms(start, 101)), Some(true));
+        assert_eq!(shift.update(false, true, ms(start, 110)), Some(false));
+    }
+
+    #[test]
+    fn test_das_cancel_on_direction_change() {
+        let mut shift = ShiftState::new(100, 50);
+        let start = Instant::now();
+        shift.update(true, false, start);
+        assert_eq!(shift.update(true, false, ms(start, 101)), Some(true));
+        // charged left, switch to right: right does not inherit the charge
+        assert_eq!(shift.update(false, true, ms(start, 110)), Some(false));
+        assert_eq!(shift.update(false, true, ms(start, 170)), None);
+        // back to left: it has to charge again too
+        assert_eq!(shift.update(true, false, ms(start, 180)), Some(true));
+        assert_eq!(shift.update(true, false, ms(start, 240)), None);
+    }
+
+    #[test]
+    fn test_das_charge_across_spawns() {
+        let start = Instant::now();
+        let mut shift = ShiftState::new(100, 50);
+        shift.update(true, false, start);
+        assert_eq!(shift.update(true, false, ms(start, 101)), Some(true));
+        // a new piece while left is held: it keeps going at the auto repeat rate
+        shift.on_spawn();
+        assert_eq!(shift.update(true, false, ms(start, 152)), Some(true));
+
+        // without keeping the charge the new piece gets a tap and waits for DAS again
+        let mut shift = ShiftState::new(100, 50);
+        shift.set_keep_charge(false);
+        shift.update(true, false, start);
+        assert_eq!(shift.update(true, false, ms(start, 101)), Some(true));
+        shift.on_spawn();
+        assert_eq!(shift.update(true, false, ms(start, 110)), Some(true));
+        assert_eq!(shift.update(true, false, ms(start, 170)), None);
+        assert_eq!(shift.update(true, false, ms(start, 211)), Some(true));
+    }
+
     #[test]
     fn test_release_reset() {
         let mut motion = MotionState::new(100, 50);
@@ -1,2 +1,19 @@
 mod input; // no need be known for main.rs
 pub mod game;
+pub mod rotation;
+pub mod mode;
+pub mod classic;
+pub mod randomizer;
+pub mod garbage;
+pub mod attack;
+pub mod versus;
+pub mod net;
+pub mod pathfind;
+pub mod bot;
+pub mod finesse;
+pub mod tbp;
+pub mod bindings;
+pub mod source;
+pub mod gamepad;
+pub mod replay;
+pub mod cli;
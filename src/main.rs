@@ -1,8 +1,20 @@
 
 // use is shortcut. the place we define the mod is in the lib.rs by using mod game and mod input
-use minifb::{Key, Window, WindowOptions};
-use std::time::{Instant};
-use tetris::game::{Board, GameCommand, Tetromino, create_new_game};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use std::time::{Duration, Instant};
+use tetris::game::{Board, GameCommand, GameState, InputState, RandomGenerator, RotationSystem, Rules, Tetromino, check_board_size, create_custom_game};
+use tetris::bindings::{Bindings, DEFAULT_SECTION};
+use tetris::cli::{arg_value, exit_with, new_game};
+use tetris::bot::{Bot, BotPlayer};
+use tetris::tbp::{TbpBot, TbpPlayer};
+use tetris::gamepad::{Gamepad, PAD_DEFAULTS, PadInput, find_device};
+use tetris::randomizer::SequenceGenerator;
+use tetris::garbage::GarbageGenerator;
+use tetris::source::{InputSource, Keyboard, merge};
+use tetris::replay::{Recorder, Replay, ReplayHeader};
+use tetris::net::{Client, DEFAULT_PORT, Message, PeerInput};
+use tetris::versus::{HolePolicy, Versus};
+use tetris::mode::{GameMode, GameResult, ModeStatus, Dig, Endless, Ultra, DIG_LINES, DIG_VISIBLE_ROWS, format_time, mode_by_name};
 
 // mod	Definition / Loading. Tells Rust to look for a file and include it in the compilation tree.	The Foundation. "I have a room called game."	namespace game { ... } or adding a file to your CMake project.
 // pub	Visibility / Access. Determines if a module, function, or struct can be seen from the outside.	The Door Key. "This room is open to visitors."	public: in a class or a header file exposed in an API.
@@ -11,111 +23,765 @@ use tetris::game::{Board, GameCommand, Tetromino, create_new_game};
 
 // --- 常量定義 ---
 const BLACK: u32 = 0x000000;
-const WIDTH: usize = 300;
-const HEIGHT: usize = 500;
-const BLOCK_SIZE: i32 = 20; // Tetris 方塊的像素大小
+const WHITE: u32 = 0xFFFFFF;
+const BLOCK_SIZE: i32 = 20; // Tetris 方塊的像素大小, default of --block
+const BOARD_WIDTH: i32 = 10; // default of --width, in cells
+const BOARD_HEIGHT: i32 = 20; // default of --height
+const MIN_BLOCK_SIZE: i32 = 4;
+const MAX_BLOCK_SIZE: i32 = 64;
+const MAX_WINDOW: usize = 4096; // pixels, either way
+const MAX_DELAY: i32 = 2000; // ms, for --are and --line-clear-delay
+const VERSUS_GAP: usize = 40; // space between the two boards in versus
+const METER_WIDTH: usize = 12; // incoming garbage meter on the right of each board
 
-fn draw_tertromino(buffer:&mut [u32], t:&Tetromino) {
-    for pos in t.world_cells() {
-        draw_square(buffer, pos.x * BLOCK_SIZE , pos.y * BLOCK_SIZE , t.kind.color());
+// the pixel buffer handed to minifb, with its size so several boards can share one window.
+struct Screen {
+    buffer: Vec<u32>,
+    width: usize,
+    height: usize,
+    // pixels per board cell
+    block: i32,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize, block: i32) -> Self {
+        Self { buffer: vec![0; width * height], width, height, block }
+    }
+
+    // fill a rectangle of pixels, clipped to the window
+    fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: u32) {
+        for py in y.max(0)..(y + h).min(self.height as i32) {
+            for px in x.max(0)..(x + w).min(self.width as i32) {
+                self.buffer[py as usize * self.width + px as usize] = color;
+            }
+        }
     }
 }
 
-fn draw_tertromino_with_color(buffer:&mut [u32], t:&Tetromino, color:u32) {
+fn draw_tertromino(screen: &mut Screen, ox: i32, t:&Tetromino) {
+    draw_tertromino_with_color(screen, ox, t, t.kind.color());
+}
+
+fn draw_tertromino_with_color(screen: &mut Screen, ox: i32, t:&Tetromino, color:u32) {
+    let block = screen.block;
     for pos in t.world_cells() {
-        draw_square(buffer, pos.x * BLOCK_SIZE , pos.y * BLOCK_SIZE , color);
+        draw_square(screen, ox + pos.x * block , pos.y * block , color);
+    }
+}
+
+// ox is the left edge of the board in pixels
+fn draw_board(screen: &mut Screen, ox: i32, b:&Board) {
+    let block = screen.block;
+    for y in 0..b.height {
+        for x in 0..b.width {
+            if let Some(color) = b.cells[(y * b.width + x) as usize].color() {
+                draw_square(screen, ox + x * block, y * block, color);
+            }
+        }
     }
 }
 
-fn draw_board(buffer:&mut [u32], b:&Board) {
+// The board during a line clear delay: the full rows flash for the first half, then they are gone
+// and the rows above slide down into the gap. Drawn from the progress, so inputs never wait on it.
+fn draw_clearing(screen: &mut Screen, ox: i32, b: &Board, rows: &[usize], progress: f64) {
+    let block = screen.block;
+    let flash = progress < 0.5 && (progress * 8.0) as i32 % 2 == 0;
+    let collapse = ((progress - 0.5) * 2.0).clamp(0.0, 1.0);
     for y in 0..b.height {
+        let cleared = rows.contains(&(y as usize));
+        if cleared && progress >= 0.5 {
+            continue;
+        }
+        // how far this row falls once the clear is done
+        let below = rows.iter().filter(|&&r| r as i32 > y).count() as i32;
+        let py = y * block + (f64::from(below * block) * collapse) as i32;
         for x in 0..b.width {
-            if let Some(kind) = b.cells[(y * b.width + x) as usize] {
-                draw_square(buffer, x * BLOCK_SIZE, y * BLOCK_SIZE, kind.color());
+            let color = if cleared && flash { Some(WHITE) } else { b.cells[(y * b.width + x) as usize].color() };
+            if let Some(color) = color {
+                draw_square(screen, ox + x * block, py, color);
             }
         }
     }
 }
 
+// one bar per pending garbage line, growing up from the bottom like the rows it will push in
+fn draw_meter(screen: &mut Screen, x: i32, pending: usize) {
+    let h = screen.height as i32;
+    screen.fill_rect(x, 0, METER_WIDTH as i32, h, 0x202020);
+    let bar = (pending as i32 * screen.block).min(h);
+    screen.fill_rect(x + 2, h - bar, METER_WIDTH as i32 - 4, bar, 0xFF3030);
+}
+
+fn draw_game(screen: &mut Screen, ox: i32, game: &mut GameState) {
+    let shadow = game.get_shadow();
+    let board_px = game.get_board().width * screen.block;
+    match game.get_clearing() {
+        Some((rows, progress)) => draw_clearing(screen, ox, game.get_board(), &rows, progress),
+        None => draw_board(screen, ox, game.get_board()),
+    }
+    // no piece in play during a line clear or the entry delay
+    if game.get_phase().has_piece() {
+        if let Some(shadow) = shadow {
+            draw_tertromino_with_color(screen, ox, &shadow, 0x444444); // draw shadow first
+        }
+        draw_tertromino(screen, ox, &game.current_tetris);
+    }
+    draw_meter(screen, ox + board_px, game.pending_garbage());
+}
+
 // render
-fn draw_square(buffer: &mut [u32], x: i32, y: i32, color: u32) {
+fn draw_square(screen: &mut Screen, x: i32, y: i32, color: u32) {
     // 簡單的邊界檢查
-    if x < 0 || y < 0 || x + BLOCK_SIZE > WIDTH as i32 || y + BLOCK_SIZE > HEIGHT as i32 {
+    let block = screen.block;
+    if x < 0 || y < 0 || x + block > screen.width as i32 || y + block > screen.height as i32 {
         return;
     }
+    screen.fill_rect(x, y, block, block, color);
+}
+
+// 3x5 pixel font, one u8 per row, the low 3 bits are the pixels from left to right.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => [0; 5],
+    }
+}
 
-    for row in 0..BLOCK_SIZE {
-        for col in 0..BLOCK_SIZE {
-            let px = (x + col) as usize;
-            let py = (y + row) as usize;
-            let index = py * WIDTH + px;
-            if index < buffer.len() {
-                 buffer[index] = color;
+// each font pixel becomes a `scale` x `scale` square, characters are 4 font pixels apart.
+fn draw_text(screen: &mut Screen, x: i32, y: i32, text: &str, scale: i32, color: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as i32 * 4 * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) != 0 {
+                    screen.fill_rect(left + col * scale, y + row as i32 * scale, scale, scale, color);
+                }
             }
         }
     }
 }
 
+fn text_width(text: &str, scale: i32) -> i32 {
+    text.chars().count() as i32 * 4 * scale - scale
+}
 
+// darken what was drawn last frame
+fn dim(screen: &mut Screen) {
+    for pixel in screen.buffer.iter_mut() {
+        *pixel = (*pixel >> 2) & 0x3F3F3F;
+    }
+}
 
+// lines of text centered on the screen
+fn draw_centered(screen: &mut Screen, lines: &[String], scale: i32) {
+    let top = (screen.height as i32 - lines.len() as i32 * 8 * scale) / 2;
+    for (i, line) in lines.iter().enumerate() {
+        let x = (screen.width as i32 - text_width(line, scale)) / 2;
+        draw_text(screen, x, top + i as i32 * 8 * scale, line, scale, 0xFFFFFF);
+    }
+}
 
-fn main() {
+// list the final stats on top of the last frame.
+fn draw_end_screen(screen: &mut Screen, game: &GameState) {
+    dim(screen);
+    let stats = game.get_stats();
+    let heading = match game.get_status() {
+        ModeStatus::Finished => "CLEAR",
+        _ => "GAME OVER",
+    };
+    let lines = [
+        game.get_mode_name().to_string(),
+        heading.to_string(),
+        String::new(),
+        format!("SCORE {}", stats.score),
+        format!("LINES {}", stats.lines),
+        format!("LEVEL {}", stats.level),
+        format!("TIME {}", format_time(stats.elapsed)),
+        format!("PPS {:.2}", stats.pps()),
+    ];
+    // smaller text on narrow boards
+    draw_centered(screen, &lines, (screen.width as i32 / 70).clamp(1, 3));
+}
+
+// append one line per finished game, writing the header for a new file.
+fn export_result(path: &str, game: &GameState) -> std::io::Result<()> {
+    use std::io::Write;
+    let new_file = !std::path::Path::new(path).exists();
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    if new_file {
+        writeln!(file, "{}", GameResult::CSV_HEADER)?;
+    }
+    writeln!(file, "{}", game.get_result().to_csv())
+}
+
+// value following `flag` on the command line, e.g. --mode sprint
+fn number_arg(args: &[String], flag: &str, default: i32) -> i32 {
+    match arg_value(args, flag) {
+        Some(v) => v.parse().unwrap_or_else(|_| exit_with(format!("invalid {} '{}', expected a number", flag, v))),
+        None => default,
+    }
+}
+
+// --sequence <pieces> (letters like "IOTJLSZ") or --sequence-file <file> holding them
+fn sequence_arg(args: &[String]) -> Option<SequenceGenerator> {
+    let pieces = match (arg_value(args, "--sequence"), arg_value(args, "--sequence-file")) {
+        (None, None) => return None,
+        (Some(_), Some(_)) => exit_with("--sequence and --sequence-file cannot be used together".to_string()),
+        (Some(text), None) => SequenceGenerator::parse(text),
+        (None, Some(path)) => SequenceGenerator::load(path),
+    };
+    Some(pieces.unwrap_or_else(|e| exit_with(format!("invalid sequence: {}", e))))
+}
+
+// --are <ms> and --line-clear-delay <ms>, 0 for none (the default, except in the NES mode)
+fn delay_arg(args: &[String], flag: &str) -> Duration {
+    let ms = number_arg(args, flag, 0);
+    if !(0..=MAX_DELAY).contains(&ms) {
+        exit_with(format!("{} {} is out of range 0..={}", flag, ms, MAX_DELAY));
+    }
+    Duration::from_millis(ms as u64)
+}
+
+// board size in cells and the size of a cell in pixels: --width, --height, --block
+#[derive(Clone, Copy)]
+struct Layout {
+    width: i32,
+    height: i32,
+    block: i32,
+}
+
+impl Layout {
+    // one board and its garbage meter
+    fn board_px(&self) -> usize {
+        (self.width * self.block) as usize + METER_WIDTH
+    }
+
+    // room for `boards` boards side by side, VERSUS_GAP apart
+    fn screen(&self, boards: usize) -> Screen {
+        let width = self.board_px() * boards + VERSUS_GAP * (boards - 1);
+        let height = (self.height * self.block) as usize;
+        if width > MAX_WINDOW || height > MAX_WINDOW {
+            exit_with(format!("a {}x{} pixel window is too big, use a smaller --block", width, height));
+        }
+        Screen::new(width, height, self.block)
+    }
+}
 
-    let mut buffer: Vec<u32> = vec![0; WIDTH * HEIGHT];
+fn parse_layout(args: &[String]) -> Layout {
+    let layout = Layout {
+        width: number_arg(args, "--width", BOARD_WIDTH),
+        height: number_arg(args, "--height", BOARD_HEIGHT),
+        block: number_arg(args, "--block", BLOCK_SIZE),
+    };
+    if let Err(e) = check_board_size(layout.width, layout.height) {
+        exit_with(e);
+    }
+    if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&layout.block) {
+        exit_with(format!("--block {} is out of range {}..={}", layout.block, MIN_BLOCK_SIZE, MAX_BLOCK_SIZE));
+    }
+    layout
+}
 
+// --mode <name> (defaults to endless), --time <seconds> for ultra, --messiness <0..1> for dig
+fn parse_mode(args: &[String]) -> Box<dyn GameMode> {
+    let Some(name) = arg_value(args, "--mode") else {
+        return Box::new(Endless);
+    };
+    if let Some(messiness) = arg_value(args, "--messiness") {
+        if !name.eq_ignore_ascii_case("dig") {
+            exit_with("--messiness only applies to --mode dig".to_string());
+        }
+        // the range check also turns away nan and inf, which parse() accepts
+        let messiness: f64 = messiness.parse().ok().filter(|m| (0.0..=1.0).contains(m))
+            .unwrap_or_else(|| exit_with(format!("invalid --messiness '{}', expected 0.0 to 1.0", messiness)));
+        return Box::new(Dig::new(DIG_LINES, DIG_VISIBLE_ROWS, GarbageGenerator::new(messiness)));
+    }
+    if let Some(secs) = arg_value(args, "--time") {
+        if !name.eq_ignore_ascii_case("ultra") {
+            exit_with("--time only applies to --mode ultra".to_string());
+        }
+        let secs: u64 = secs.parse().unwrap_or_else(|_| exit_with(format!("invalid --time '{}'", secs)));
+        return Box::new(Ultra::new(Duration::from_secs(secs)));
+    }
+    mode_by_name(name).unwrap_or_else(|| {
+        exit_with(format!("unknown mode '{}', expected endless, sprint, ultra, marathon, dig or nes", name))
+    })
+}
+
+// no text rendering in minifb, so the timer and line count live in the title bar.
+fn title(game: &GameState) -> String {
+    let stats = game.get_stats();
+    let paused = if game.is_paused() { " - PAUSED" } else { "" };
+    let hold = game.get_hold().map_or("-".to_string(), |kind| format!("{:?}", kind));
+    let finesse = if game.counts_finesse() { format!(" - finesse {}", stats.finesse_faults) } else { String::new() };
+    format!("Rust Tetris - {} - {} lines - score {} - {} - hold {}{}{}",
+        game.get_mode_name(), stats.lines, stats.score, format_time(stats.elapsed), hold, finesse, paused)
+}
+
+// default controls, a --keys file can change them
+type KeyMap = [(Key, GameCommand); 6];
+
+// a bot at the controls: the presses for this frame
+type Autopilot = Box<dyn FnMut(&GameState) -> Vec<(bool, GameCommand)>>;
+
+const KEYS: KeyMap = [
+    (Key::Left, GameCommand::MoveLeft),
+    (Key::Right, GameCommand::MoveRight),
+    (Key::Down, GameCommand::SoftDrop),
+    (Key::LeftCtrl, GameCommand::Rotate),
+    (Key::Space, GameCommand::HardDrop),
+    (Key::C, GameCommand::Hold),
+];
+
+// versus: player 1 on the left uses WASD + space (Q holds), player 2 the arrows + enter (right shift holds)
+const P1_KEYS: KeyMap = [
+    (Key::A, GameCommand::MoveLeft),
+    (Key::D, GameCommand::MoveRight),
+    (Key::S, GameCommand::SoftDrop),
+    (Key::W, GameCommand::Rotate),
+    (Key::Space, GameCommand::HardDrop),
+    (Key::Q, GameCommand::Hold),
+];
+const P2_KEYS: KeyMap = [
+    (Key::Left, GameCommand::MoveLeft),
+    (Key::Right, GameCommand::MoveRight),
+    (Key::Down, GameCommand::SoftDrop),
+    (Key::Up, GameCommand::Rotate),
+    (Key::Enter, GameCommand::HardDrop),
+    (Key::RightShift, GameCommand::Hold),
+];
+
+// every key a command can be bound to. Escape quits and P pauses, so they are left out.
+const BINDABLE_KEYS: [Key; 104] = {
+    use Key::*;
+    [
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, Q, R, S, T, U, V, W, X, Y, Z,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15,
+        Down, Left, Right, Up, Apostrophe, Backquote, Backslash, Comma, Equal, LeftBracket, Minus,
+        Period, RightBracket, Semicolon, Slash, Backspace, Delete, End, Enter, Home, Insert, Menu,
+        PageDown, PageUp, Pause, Space, Tab, NumLock, CapsLock, ScrollLock,
+        LeftShift, RightShift, LeftCtrl, RightCtrl, LeftAlt, RightAlt, LeftSuper, RightSuper,
+        NumPad0, NumPad1, NumPad2, NumPad3, NumPad4, NumPad5, NumPad6, NumPad7, NumPad8, NumPad9,
+        NumPadDot, NumPadSlash, NumPadAsterisk, NumPadMinus, NumPadPlus, NumPadEnter,
+    ]
+};
+
+// key names are the minifb names ("Left", "LeftCtrl", "NumPad4"), any case; "7" works for Key7
+fn key_from_name(name: &str) -> Option<Key> {
+    BINDABLE_KEYS.into_iter().find(|key| {
+        let debug = format!("{:?}", key);
+        debug.eq_ignore_ascii_case(name) || debug.strip_prefix("Key") == Some(name)
+    })
+}
+
+// `defaults` with the lines of `section` from the --keys file on top
+fn key_bindings(config: Option<&str>, section: &str, defaults: &KeyMap) -> Bindings<Key> {
+    let mut bindings = Bindings::new(defaults);
+    if let Some(text) = config
+        && let Err(e) = bindings.load(text, section, key_from_name) {
+        exit_with(format!("bad --keys file: {}", e));
+    }
+    bindings
+}
+
+// --gamepad [device]: play with a joystick device too (the first /dev/input/js* by default),
+// buttons are bound in the [gamepad] section of the --keys file
+fn open_gamepad(args: &[String], config: Option<&str>) -> Option<Gamepad> {
+    let device = arg_value(args, "--gamepad")?;
+    let path = if device.is_empty() || device.starts_with("--") {
+        find_device().unwrap_or_else(|| exit_with("no gamepad found in /dev/input".to_string()))
+    } else {
+        device.into()
+    };
+    let mut bindings = Bindings::new(&PAD_DEFAULTS);
+    if let Some(text) = config
+        && let Err(e) = bindings.load(text, "gamepad", PadInput::from_name) {
+        exit_with(format!("bad --keys file: {}", e));
+    }
+    let pad = Gamepad::open(&path, bindings)
+        .unwrap_or_else(|e| exit_with(format!("cannot open gamepad {}: {}", path.display(), e)));
+    Some(pad)
+}
+
+// --record <file>: save the game to a replay file, --replay <file>: watch one (see replay.rs).
+// A replay only knows the name of the mode, so the options that tune a mode are left out.
+fn open_replay(args: &[String], layout: &mut Layout) -> Option<Replay> {
+    let replaying = arg_value(args, "--replay");
+    if replaying.is_none() && arg_value(args, "--record").is_none() {
+        return None;
+    }
+    for flag in ["--time", "--messiness", "--finesse", "--bot", "--tbp", "--are", "--line-clear-delay", "--rotation", "--randomizer", "--sequence", "--sequence-file"] {
+        if args.iter().any(|a| a == flag) {
+            exit_with(format!("{} cannot be used with --record or --replay", flag));
+        }
+    }
+    let path = replaying?;
+    let replay = Replay::load(path).unwrap_or_else(|e| exit_with(format!("bad replay '{}': {}", path, e)));
+    layout.width = replay.header.width;
+    layout.height = replay.header.height;
+    Some(replay)
+}
+
+fn open_window(width: usize, height: usize) -> Window {
     let mut window = Window::new(
         "Rust Tetris",
-        WIDTH,
-        HEIGHT,
+        width,
+        height,
         WindowOptions::default(),
     )
     .unwrap_or_else(|e| {
         panic!("window creation fail: {}", e);
     });
 
-
     // 60fps update
     window.set_target_fps(60); 
+    window
+}
 
-    let keys = vec![Key::Left, Key::Right, Key::Down, Key::LeftCtrl, Key::Space];
+// --versus [per-row|<messiness 0..1>]: two players on one keyboard
+fn parse_hole_policy(args: &[String]) -> Option<HolePolicy> {
+    let value = arg_value(args, "--versus")?;
+    Some(match value {
+        v if v.is_empty() || v.starts_with("--") => HolePolicy::default(),
+        "per-row" => HolePolicy::PerRow,
+        v => v.parse::<f64>().ok().filter(|m| (0.0..=1.0).contains(m)).map(HolePolicy::PerAttack)
+            .unwrap_or_else(|| exit_with(format!("invalid --versus hole policy '{}', expected per-row or 0.0 to 1.0", v))),
+    })
+}
 
+fn run_versus(layout: Layout, policy: HolePolicy, keys: [Bindings<Key>; 2]) {
+    let mut keyboards = keys.map(Keyboard::new);
+    let board_px = layout.board_px();
+    let mut screen = layout.screen(2);
+    let mut window = open_window(screen.width, screen.height);
+    window.set_title("Rust Tetris - Versus");
 
-    let mut game = 
-        create_new_game(WIDTH as i32 / BLOCK_SIZE, HEIGHT as i32 / BLOCK_SIZE, Instant::now());
+    let mut versus = Versus::new(layout.width, layout.height, Instant::now(), policy).unwrap_or_else(|e| exit_with(e));
+    let origins = [0, (board_px + VERSUS_GAP) as i32];
+    let mut reported = false;
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
+        if versus.is_over() {
+            if !reported {
+                reported = true;
+                let result = match versus.winner() {
+                    Some(player) => format!("PLAYER {} WINS", player + 1),
+                    None => "DRAW".to_string(),
+                };
+                println!("Versus: {}", result.to_lowercase());
+                dim(&mut screen);
+                draw_centered(&mut screen, &[result], 4);
+                window.update_with_buffer(&screen.buffer, screen.width, screen.height).unwrap();
+            } else {
+                window.update();
+            }
+            continue;
+        }
+
+        let now = Instant::now();
+        for (player, keyboard) in keyboards.iter_mut().enumerate() {
+            keyboard.read(&window);
+            versus.update_frame(player, &InputState::from_states(&keyboard.poll()), now);
+        }
+        versus.exchange();
 
+        screen.buffer.fill(BLACK);
+        screen.fill_rect(board_px as i32, 0, VERSUS_GAP as i32, screen.height as i32, 0x202020);
+        for (player, &ox) in origins.iter().enumerate() {
+            draw_game(&mut screen, ox, versus.game_mut(player));
+        }
+        window
+            .update_with_buffer(&screen.buffer, screen.width, screen.height)
+            .unwrap();
+    }
+}
+
+// --connect <host[:port]> [--name <name>]: versus against another client through tetris-server.
+// The local game is on the left, the opponent's last board summary on the right and the keys it
+// holds down in the title.
+fn run_network(layout: Layout, addr: &str, name: &str, keys: Bindings<Key>) {
+    let addr = if addr.contains(':') { addr.to_string() } else { format!("{}:{}", addr, DEFAULT_PORT) };
+    println!("waiting for an opponent on {} ...", addr);
+    let mut client = Client::connect(&addr, name)
+        .unwrap_or_else(|e| exit_with(format!("cannot join {}: {}", addr, e)));
+
+    let board_px = layout.board_px();
+    let mut screen = layout.screen(2);
+    let mut window = open_window(screen.width, screen.height);
+
+    let (width, height) = (layout.width, layout.height);
+    // same seed on both sides: same pieces
+    let mut game = create_custom_game(width, height, Instant::now(), Box::new(Endless),
+        Box::new(RandomGenerator::with_seed(client.seed))).unwrap_or_else(|e| exit_with(e));
+    let mut holes = GarbageGenerator::new(0.7);
+    let mut opponent: Option<Board> = None;
+    let mut peer = PeerInput::default();
+    let mut last_title = String::new();
+    let mut pieces = 0;
+    let mut pressed = [false; GameCommand::ALL.len()];
+    let mut keyboard = Keyboard::new(keys);
+    let mut result: Option<&str> = None;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        for msg in client.poll() {
+            peer.handle(&msg);
+            match msg {
+                Message::Attack { lines, hole } => game.receive_garbage(lines.min(game.board.height as usize), hole),
+                Message::Board(board) => opponent = Some(board),
+                Message::GameOver => result = result.or(Some("YOU WIN")),
+                _ => {}
+            }
+        }
+        if !client.is_connected() {
+            result = result.or(Some("OPPONENT LEFT"));
+        }
+        if let Some(text) = result {
+            dim(&mut screen);
+            draw_centered(&mut screen, &[text.to_string()], 4);
+            window.update_with_buffer(&screen.buffer, screen.width, screen.height).unwrap();
+            // keep the final screen until Escape
+            while window.is_open() && !window.is_key_down(Key::Escape) {
+                window.update();
+            }
+            println!("{}", text.to_lowercase());
+            return;
+        }
+
+        let now = Instant::now();
+        keyboard.read(&window);
+        let states = keyboard.poll();
+        for (i, &(down, command)) in states.iter().enumerate() {
+            if down != pressed[i] {
+                pressed[i] = down;
+                let _ = client.send(&Message::Input { command, pressed: down });
+            }
+        }
+        game.update_frame(&InputState::from_states(&states), now);
+        let sent = game.take_attack();
+        if sent > 0 {
+            let _ = client.send(&Message::Attack { lines: sent, hole: holes.next_hole(width) });
+        }
+        if game.get_stats().pieces != pieces {
+            pieces = game.get_stats().pieces;
+            let _ = client.send(&Message::Board(game.get_board().clone()));
+        }
         if game.get_game_over() {
-            window.update(); // otherwise I cannot read the Escape
+            let _ = client.send(&Message::GameOver);
+            result = Some("YOU LOSE");
+        }
+
+        screen.buffer.fill(BLACK);
+        screen.fill_rect(board_px as i32, 0, VERSUS_GAP as i32, screen.height as i32, 0x202020);
+        draw_game(&mut screen, 0, &mut game);
+        if let Some(board) = &opponent {
+            draw_board(&mut screen, (board_px + VERSUS_GAP) as i32, board);
+        }
+        let held: Vec<&str> = peer.poll().into_iter().filter(|&(down, _)| down).map(|(_, command)| command.name()).collect();
+        let held = if held.is_empty() { "-".to_string() } else { held.join(" ") };
+        let new_title = format!("Rust Tetris - {} (player {}) - opponent holds {}", name, client.player + 1, held);
+        if new_title != last_title {
+            window.set_title(&new_title);
+            last_title = new_title;
+        }
+        window
+            .update_with_buffer(&screen.buffer, screen.width, screen.height)
+            .unwrap();
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mode = parse_mode(&args);
+    let mut layout = parse_layout(&args);
+    let mut replay = open_replay(&args, &mut layout);
+    let record = arg_value(&args, "--record");
+    // --keys <file>: rebind the controls, see bindings.rs for the format
+    let key_config = arg_value(&args, "--keys").map(|path| {
+        std::fs::read_to_string(path).unwrap_or_else(|e| exit_with(format!("cannot read --keys file '{}': {}", path, e)))
+    });
+    let key_config = key_config.as_deref();
+    // --leaderboard <file>: append the final stats of the game as csv
+    let leaderboard = arg_value(&args, "--leaderboard");
+    // --bot: sit back and watch the bot play, the keyboard only pauses and quits
+    // --tbp "<command>": same with an external Tetris Bot Protocol bot
+    let mut autopilot: Option<Autopilot> = None;
+    if args.iter().any(|a| a == "--bot") {
+        let mut bot = BotPlayer::new(Bot::default());
+        autopilot = Some(Box::new(move |game| bot.inputs(game)));
+    }
+    let tbp = arg_value(&args, "--tbp");
+    if let Some(command) = tbp {
+        if layout.width != 10 {
+            exit_with("TBP bots play on a 10 wide board, leave out --width".to_string());
+        }
+        let bot = TbpBot::spawn(command).unwrap_or_else(|e| exit_with(format!("cannot start bot '{}': {}", command, e)));
+        println!("playing with {}", bot.name);
+        let mut player = TbpPlayer::new(bot);
+        autopilot = Some(Box::new(move |game| player.inputs(game)));
+    }
+    if let Some(policy) = parse_hole_policy(&args) {
+        let keys = [key_bindings(key_config, "player1", &P1_KEYS), key_bindings(key_config, "player2", &P2_KEYS)];
+        run_versus(layout, policy, keys);
+        return;
+    }
+    if let Some(addr) = arg_value(&args, "--connect") {
+        let keys = key_bindings(key_config, DEFAULT_SECTION, &KEYS);
+        run_network(layout, addr, arg_value(&args, "--name").unwrap_or("player"), keys);
+        return;
+    }
+
+    let mut keyboard = Keyboard::new(key_bindings(key_config, DEFAULT_SECTION, &KEYS));
+    let mut gamepad = open_gamepad(&args, key_config);
+    let mut screen = layout.screen(1);
+    let mut window = open_window(screen.width, screen.height);
+
+    let start = Instant::now();
+    let mut recorder = None;
+    let (mode, seed) = if let Some(replay) = &replay {
+        let h = &replay.header;
+        let mode = h.game_mode().unwrap_or_else(|| exit_with(format!("unknown mode '{}' in the replay", h.mode)));
+        (mode, Some(h.seed))
+    } else if record.is_some() {
+        // a seed of our own so the replay gets the same pieces
+        let seed = rand::random();
+        let name = arg_value(&args, "--mode").unwrap_or("endless").to_string();
+        let header = ReplayHeader { seed, width: layout.width, height: layout.height, mode: name };
+        // the mode again, with its garbage from the seed too
+        let mode = header.game_mode().unwrap_or(mode);
+        recorder = Some(Recorder::new(header));
+        (mode, Some(seed))
+    } else {
+        (mode, None)
+    };
+    let mut game = new_game(layout.width, layout.height, mode, arg_value(&args, "--randomizer"), sequence_arg(&args), seed, start)
+        .unwrap_or_else(|e| exit_with(e));
+    // --finesse: practice, a piece placed with extra presses goes back to the top
+    game.set_finesse_practice(args.iter().any(|a| a == "--finesse"));
+    // --show-finesse: count the faults in the title without practice
+    game.set_track_finesse(args.iter().any(|a| a == "--show-finesse"));
+    if arg_value(&args, "--are").is_some() {
+        game.set_entry_delay(delay_arg(&args, "--are"));
+    }
+    if arg_value(&args, "--line-clear-delay").is_some() {
+        game.set_line_clear_delay(delay_arg(&args, "--line-clear-delay"));
+    }
+    // --rotation srs|ars|nrs, SRS by default and NRS in the NES mode
+    if let Some(name) = arg_value(&args, "--rotation") {
+        let rotation = RotationSystem::from_name(name)
+            .unwrap_or_else(|| exit_with(format!("unknown rotation system '{}', expected srs, ars or nrs", name)));
+        game.set_rules(Rules { rotation, ..game.get_rules() });
+    }
+    let mut reported = false;
+    let mut last_title = String::new();
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+
+        if game.get_game_over() {
+            if !reported {
+                reported = true;
+                let summary = game.get_summary();
+                println!("{}: {}", game.get_mode_name(), summary);
+                window.set_title(&format!("Rust Tetris - {} - {}", game.get_mode_name(), summary));
+                if let Some(path) = leaderboard
+                    && let Err(e) = export_result(path, &game) {
+                    eprintln!("cannot write leaderboard '{}': {}", path, e);
+                }
+                draw_end_screen(&mut screen, &game);
+                window.update_with_buffer(&screen.buffer, screen.width, screen.height).unwrap();
+            } else {
+                window.update(); // otherwise I cannot read the Escape
+            }
             continue;
         }
 
 
         let now = Instant::now();
 
-        for &key in keys.iter() {
-            use GameCommand::*;
-            let command = match key {
-                Key::Left => MoveLeft, 
-                Key::Right => MoveRight,
-                Key::Down => SoftDrop,
-                Key::LeftCtrl => Rotate,
-                Key::Space => HardDrop,
-                _ => None
-            };
-            game.update(window.is_key_down(key), command, now);
-        }
-
-        let shadow = game.get_shadow();
-        buffer.fill(BLACK); // clean all 
-        draw_board(&mut buffer, &game.get_board());
-        if let Some(shadow) = shadow {
-            draw_tertromino_with_color(&mut buffer, &shadow, 0x444444); // draw shadow first
+        // no pausing a recording, the replay would not know
+        if recorder.is_none() && replay.is_none() && window.is_key_pressed(Key::P, KeyRepeat::No) {
+            if game.is_paused() { game.resume(now) } else { game.pause(now) }
         }
-        draw_tertromino(&mut buffer, &game.current_tetris);
 
+        if let Some(replay) = replay.as_mut() {
+            // the recorded frames at their recorded times, then the game just sits there
+            if !replay.is_finished() {
+                let input = InputState::from_states(&replay.poll());
+                game.update_frame(&input, start + replay.elapsed());
+            }
+        } else if let Some(autopilot) = autopilot.as_mut() {
+            // paused games ignore the frame anyway, but the bot should not plan for it
+            if !game.is_paused() {
+                game.update_frame(&InputState::from_states(&autopilot(&game)), now);
+            }
+        } else {
+            keyboard.read(&window);
+            let mut states = vec![keyboard.poll()];
+            if let Some(pad) = gamepad.as_mut() {
+                states.push(pad.poll());
+            }
+            let states = merge(&states);
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(now - start, &states);
+            }
+            game.update_frame(&InputState::from_states(&states), now);
+        }
+
+        screen.buffer.fill(BLACK); // clean all 
+        draw_game(&mut screen, 0, &mut game);
+
+        let new_title = title(&game);
+        if new_title != last_title {
+            window.set_title(&new_title);
+            last_title = new_title;
+        }
         window
-            .update_with_buffer(&buffer, WIDTH, HEIGHT)
+            .update_with_buffer(&screen.buffer, screen.width, screen.height)
             .unwrap();
     }
-}
\ No newline at end of file
+    if let (Some(recorder), Some(path)) = (recorder, record) {
+        match recorder.save(path) {
+            Ok(()) => println!("replay saved to {}", path),
+            Err(e) => eprintln!("cannot write replay '{}': {}", path, e),
+        }
+    }
+}
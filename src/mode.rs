@@ -0,0 +1,421 @@
+use std::time::Duration;
+
+use crate::classic::Classic;
+use crate::game::{Board, GameStats};
+use crate::garbage::GarbageGenerator;
+
+// What the mode thinks about the current game.
+// Failed is reserved for modes that can be lost by something other than topping out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeStatus {
+    Playing,
+    Finished,
+    Failed,
+}
+
+// A game mode is a small rule object that GameState consults for win/lose conditions.
+// GameState owns the clock and the counters (GameStats); the mode only reads them.
+pub trait GameMode {
+    fn name(&self) -> &'static str;
+
+    // called every update and right after a piece locks.
+    fn check(&self, stats: &GameStats) -> ModeStatus;
+
+    // one line report shown when the game ends.
+    fn summary(&self, stats: &GameStats) -> String;
+
+    // level the game starts at, GameState raises it every LINES_PER_LEVEL lines.
+    fn start_level(&self) -> usize {
+        1
+    }
+
+    // time per row at `level`. None keeps the default gravity of the game.
+    fn gravity(&self, _level: usize) -> Option<Duration> {
+        None
+    }
+
+    // points for clearing `lines` lines at once at `level`, a point a line unless the mode
+    // has a score table of its own.
+    fn line_score(&self, lines: usize, _level: usize) -> usize {
+        lines
+    }
+
+    // points for a soft drop (or hard drop) of `rows` rows, none by default.
+    fn drop_score(&self, _rows: usize, _hard: bool) -> usize {
+        0
+    }
+
+    // points at the lock for a piece soft dropped `rows` rows with the key held down to the
+    // lock, none by default.
+    fn push_down_score(&self, _rows: usize) -> usize {
+        0
+    }
+
+    // prepare the empty board before the first piece spawns.
+    fn setup(&mut self, _board: &mut Board) {}
+
+    // called after every lock, once full lines have been cleared.
+    // Returns false if the mode pushed blocks out of the top, the game is lost then.
+    fn on_lock(&mut self, _board: &mut Board, _lines_cleared: usize) -> bool {
+        true
+    }
+}
+
+// guideline points: 100, 300, 500, 800 for 1 to 4 lines, times the level
+pub fn guideline_line_score(lines: usize, level: usize) -> usize {
+    let points = match lines {
+        0 => 0,
+        1 => 100,
+        2 => 300,
+        3 => 500,
+        _ => 800,
+    };
+    points * level
+}
+
+// guideline curve: (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row
+pub fn guideline_gravity(level: usize) -> Duration {
+    let l = level.clamp(1, 20) as f64 - 1.0;
+    Duration::from_secs_f64((0.8 - l * 0.007).powf(l))
+}
+
+// how a game ended, exported one line per game for the leaderboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameResult {
+    pub mode: &'static str,
+    pub status: ModeStatus,
+    pub stats: GameStats,
+}
+
+impl GameResult {
+    pub const CSV_HEADER: &'static str = "mode,status,score,lines,level,pieces,time_ms";
+
+    pub fn to_csv(&self) -> String {
+        let status = match self.status {
+            ModeStatus::Playing => "playing",
+            ModeStatus::Finished => "finished",
+            ModeStatus::Failed => "failed",
+        };
+        format!("{},{},{},{},{},{},{}", self.mode, status, self.stats.score, self.stats.lines,
+            self.stats.level, self.stats.pieces, self.stats.elapsed.as_millis())
+    }
+}
+
+// m:ss.mmm, precise to the millisecond like most sprint timers.
+pub fn format_time(t: Duration) -> String {
+    let ms = t.as_millis();
+    format!("{}:{:02}.{:03}", ms / 60_000, (ms / 1000) % 60, ms % 1000)
+}
+
+// the original behaviour: play until topping out.
+pub struct Endless;
+
+impl GameMode for Endless {
+    fn name(&self) -> &'static str {
+        "Endless"
+    }
+    fn check(&self, _stats: &GameStats) -> ModeStatus {
+        ModeStatus::Playing
+    }
+    fn summary(&self, stats: &GameStats) -> String {
+        format!("{} lines, score {} in {}", stats.lines, stats.score, format_time(stats.elapsed))
+    }
+}
+
+pub const SPRINT_LINES: usize = 40;
+
+// clear `line_goal` lines as fast as possible.
+pub struct Sprint {
+    line_goal: usize,
+}
+
+impl Sprint {
+    pub fn new(line_goal: usize) -> Self {
+        Self { line_goal }
+    }
+}
+
+impl Default for Sprint {
+    fn default() -> Self {
+        Self::new(SPRINT_LINES)
+    }
+}
+
+impl GameMode for Sprint {
+    fn name(&self) -> &'static str {
+        "Sprint"
+    }
+    fn check(&self, stats: &GameStats) -> ModeStatus {
+        if stats.lines >= self.line_goal { ModeStatus::Finished } else { ModeStatus::Playing }
+    }
+    fn summary(&self, stats: &GameStats) -> String {
+        if stats.lines >= self.line_goal {
+            format!("{}L in {} ({:.2} PPS)", self.line_goal, format_time(stats.elapsed), stats.pps())
+        } else {
+            format!("{}/{} lines, {:.2} PPS", stats.lines, self.line_goal, stats.pps())
+        }
+    }
+}
+
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+
+// score as much as possible before the time runs out.
+pub struct Ultra {
+    duration: Duration,
+}
+
+impl Ultra {
+    pub fn new(duration: Duration) -> Self {
+        Self { duration }
+    }
+}
+
+impl Default for Ultra {
+    fn default() -> Self {
+        Self::new(ULTRA_TIME)
+    }
+}
+
+impl GameMode for Ultra {
+    fn name(&self) -> &'static str {
+        "Ultra"
+    }
+    fn check(&self, stats: &GameStats) -> ModeStatus {
+        if stats.elapsed >= self.duration { ModeStatus::Finished } else { ModeStatus::Playing }
+    }
+    fn summary(&self, stats: &GameStats) -> String {
+        format!("score {} ({} lines) in {}", stats.score, stats.lines, format_time(stats.elapsed.min(self.duration)))
+    }
+    // scored like the guideline: line clears by the table, 1 point per soft dropped row, 2 per hard dropped one
+    fn line_score(&self, lines: usize, level: usize) -> usize {
+        guideline_line_score(lines, level)
+    }
+    fn drop_score(&self, rows: usize, hard: bool) -> usize {
+        if hard { 2 * rows } else { rows }
+    }
+}
+
+pub const MARATHON_LINES: usize = 150;
+
+// classic marathon: gravity follows the level, game is cleared at 150 lines (end of level 15).
+pub struct Marathon {
+    line_goal: usize,
+    start_level: usize,
+}
+
+impl Marathon {
+    pub fn new(line_goal: usize, start_level: usize) -> Self {
+        Self { line_goal, start_level: start_level.max(1) }
+    }
+}
+
+impl Default for Marathon {
+    fn default() -> Self {
+        Self::new(MARATHON_LINES, 1)
+    }
+}
+
+impl GameMode for Marathon {
+    fn name(&self) -> &'static str {
+        "Marathon"
+    }
+    fn check(&self, stats: &GameStats) -> ModeStatus {
+        if stats.lines >= self.line_goal { ModeStatus::Finished } else { ModeStatus::Playing }
+    }
+    fn summary(&self, stats: &GameStats) -> String {
+        format!("level {}, {}/{} lines, score {} in {}",
+            stats.level, stats.lines, self.line_goal, stats.score, format_time(stats.elapsed))
+    }
+    fn start_level(&self) -> usize {
+        self.start_level
+    }
+    fn gravity(&self, level: usize) -> Option<Duration> {
+        Some(guideline_gravity(level))
+    }
+}
+
+pub const DIG_LINES: usize = 18;
+pub const DIG_VISIBLE_ROWS: usize = 10;
+pub const DIG_MESSINESS: f64 = 0.3;
+
+// cheese race: dig through `total` garbage rows. At most `visible` of them are on the
+// board at a time, the rest are pushed in from the bottom as rows get cleared.
+pub struct Dig {
+    generator: GarbageGenerator,
+    total: usize,
+    visible: usize,
+    // rows not pushed to the board yet
+    queued: usize,
+    // garbage rows still on the board after the last lock
+    on_board: usize,
+}
+
+impl Dig {
+    pub fn new(total: usize, visible: usize, generator: GarbageGenerator) -> Self {
+        Self { generator, total, visible: visible.max(1), queued: total, on_board: 0 }
+    }
+
+    // the default dig with the holes from `seed`, for a game that has to play the same again
+    pub fn with_seed(seed: u64) -> Self {
+        Self::new(DIG_LINES, DIG_VISIBLE_ROWS, GarbageGenerator::with_seed(DIG_MESSINESS, seed))
+    }
+
+    pub fn garbage_left(&self) -> usize {
+        self.queued + self.on_board
+    }
+
+    // returns false if the stack was pushed out of the top
+    fn refill(&mut self, board: &mut Board) -> bool {
+        let mut fits = true;
+        while self.on_board < self.visible && self.queued > 0 {
+            fits &= board.push_garbage_row(self.generator.next_hole(board.width));
+            self.on_board += 1;
+            self.queued -= 1;
+        }
+        fits
+    }
+}
+
+impl Default for Dig {
+    fn default() -> Self {
+        Self::new(DIG_LINES, DIG_VISIBLE_ROWS, GarbageGenerator::new(DIG_MESSINESS))
+    }
+}
+
+impl GameMode for Dig {
+    fn name(&self) -> &'static str {
+        "Dig"
+    }
+    fn check(&self, _stats: &GameStats) -> ModeStatus {
+        if self.garbage_left() == 0 { ModeStatus::Finished } else { ModeStatus::Playing }
+    }
+    fn summary(&self, stats: &GameStats) -> String {
+        let dug = self.total - self.garbage_left();
+        format!("{}/{} garbage in {}, {} pieces", dug, self.total, format_time(stats.elapsed), stats.pieces)
+    }
+    fn setup(&mut self, board: &mut Board) {
+        // keep half of a small board free, the empty board always fits them then
+        self.visible = self.visible.min(board.height as usize / 2).max(1);
+        self.refill(board);
+    }
+    fn on_lock(&mut self, board: &mut Board, _lines_cleared: usize) -> bool {
+        self.on_board = board.garbage_rows();
+        self.refill(board)
+    }
+}
+
+// factory for the command line / menus.
+pub fn mode_by_name(name: &str) -> Option<Box<dyn GameMode>> {
+    match name.to_ascii_lowercase().as_str() {
+        "endless" => Some(Box::new(Endless)),
+        "sprint" | "40l" => Some(Box::new(Sprint::default())),
+        "ultra" => Some(Box::new(Ultra::default())),
+        "marathon" => Some(Box::new(Marathon::default())),
+        "dig" | "cheese" => Some(Box::new(Dig::default())),
+        // only the gravity and scoring, classic::apply_nes_rules does the rest
+        "nes" | "classic" => Some(Box::new(Classic::default())),
+        _ => None,
+    }
+}
+
+// mode_by_name, with the random garbage of the mode (dig) from `seed` so a seed gives the same game
+pub fn seeded_mode_by_name(name: &str, seed: u64) -> Option<Box<dyn GameMode>> {
+    match name.to_ascii_lowercase().as_str() {
+        "dig" | "cheese" => Some(Box::new(Dig::with_seed(seed))),
+        _ => mode_by_name(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(Duration::from_millis(83_456)), "1:23.456");
+        assert_eq!(format_time(Duration::from_millis(5)), "0:00.005");
+    }
+
+    #[test]
+    fn test_sprint_goal() {
+        let sprint = Sprint::new(2);
+        let mut stats = GameStats::default();
+        assert_eq!(sprint.check(&stats), ModeStatus::Playing);
+        stats.lines = 2;
+        stats.pieces = 5;
+        stats.elapsed = Duration::from_secs(2);
+        assert_eq!(sprint.check(&stats), ModeStatus::Finished);
+        assert_eq!(sprint.summary(&stats), "2L in 0:02.000 (2.50 PPS)");
+    }
+
+    #[test]
+    fn test_ultra_time_up() {
+        let ultra = Ultra::new(Duration::from_secs(120));
+        let mut stats = GameStats { score: 1200, lines: 4, ..Default::default() };
+        stats.elapsed = Duration::from_millis(119_999);
+        assert_eq!(ultra.check(&stats), ModeStatus::Playing);
+        stats.elapsed = Duration::from_millis(120_010);
+        assert_eq!(ultra.check(&stats), ModeStatus::Finished);
+        assert_eq!(ultra.summary(&stats), "score 1200 (4 lines) in 2:00.000");
+    }
+
+    #[test]
+    fn test_guideline_gravity() {
+        assert_eq!(guideline_gravity(1), Duration::from_secs(1));
+        assert!(guideline_gravity(15) < Duration::from_millis(10));
+        assert!(guideline_gravity(2) < guideline_gravity(1));
+    }
+
+    #[test]
+    fn test_result_csv() {
+        let result = GameResult {
+            mode: "Marathon",
+            status: ModeStatus::Finished,
+            stats: GameStats { lines: 150, pieces: 380, score: 123456, level: 16, elapsed: Duration::from_millis(601_234), ..Default::default() },
+        };
+        assert_eq!(result.to_csv(), "Marathon,finished,123456,150,16,380,601234");
+    }
+
+    #[test]
+    fn test_dig_refill() {
+        let mut board = Board::new(10, 20);
+        let mut dig = Dig::new(5, 3, GarbageGenerator::with_seed(0.0, 1));
+        dig.setup(&mut board);
+        assert_eq!(board.garbage_rows(), 3);
+        assert_eq!(dig.garbage_left(), 5);
+
+        // clear the bottom garbage row by filling its hole
+        let hole = board.cells[19 * 10..].iter().position(|c| c.is_empty()).unwrap();
+        board.cells[19 * 10 + hole] = crate::game::Cell::Garbage;
+        board.check_clear();
+        assert!(dig.on_lock(&mut board, 1));
+        assert_eq!(board.garbage_rows(), 3);
+        assert_eq!(dig.garbage_left(), 4);
+        assert_eq!(dig.check(&GameStats::default()), ModeStatus::Playing);
+    }
+
+    #[test]
+    fn test_dig_seeded() {
+        let cheese = |seed| {
+            let mut board = Board::new(10, 20);
+            seeded_mode_by_name("dig", seed).unwrap().setup(&mut board);
+            board
+        };
+        assert_eq!(cheese(3), cheese(3));
+        assert_eq!(cheese(3).garbage_rows(), DIG_VISIBLE_ROWS);
+    }
+
+    #[test]
+    fn test_dig_overflow() {
+        // a stack reaching the top: the next garbage row pushes it out
+        let mut board = Board::from_rows(&["IIII......", "..........", "..........", "..........", ".........."]).unwrap();
+        let mut dig = Dig::new(5, 3, GarbageGenerator::with_seed(0.0, 1));
+        assert!(!dig.on_lock(&mut board, 0));
+    }
+
+    #[test]
+    fn test_mode_by_name() {
+        assert_eq!(mode_by_name("Sprint").unwrap().name(), "Sprint");
+        assert!(mode_by_name("zen").is_none());
+    }
+}
@@ -0,0 +1,398 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::game::{Board, Cell, GameCommand, MAX_BOARD_SIZE};
+use crate::source::{InputSource, states_of};
+
+pub const DEFAULT_PORT: u16 = 7777;
+// a client has this long to send its JOIN
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+// wait after a failed accept (out of file descriptors...) before the next one
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+// Versus over TCP, state-sync style: every client runs its own GameState and only tells
+// the other side what matters to it. The server pairs two clients, hands them the same
+// seed so they get the same pieces, then relays messages between them.
+//
+// One message per line, fields separated by spaces:
+//   JOIN <name>                   client -> server, first line after connecting
+//   SEED <seed> <player>          server -> client, the match starts
+//   INPUT <command> <0|1>         a key press / release, for spectators and replays
+//   ATTACK <lines> <hole>         garbage for the opponent, already cancelled by the sender
+//   BOARD <w> <h> <cells>         board summary after a lock, cells as in Cell::to_char
+//   GAMEOVER                      the sender topped out
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Join { name: String },
+    Seed { seed: u64, player: usize },
+    Input { command: GameCommand, pressed: bool },
+    Attack { lines: usize, hole: i32 },
+    Board(Board),
+    GameOver,
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Join { name } => format!("JOIN {}", name),
+            Message::Seed { seed, player } => format!("SEED {} {}", seed, player),
+            Message::Input { command, pressed } => format!("INPUT {} {}", command.name(), *pressed as u8),
+            Message::Attack { lines, hole } => format!("ATTACK {} {}", lines, hole),
+            Message::Board(board) => {
+                let cells: String = board.cells.iter().map(Cell::to_char).collect();
+                format!("BOARD {} {} {}", board.width, board.height, cells)
+            }
+            Message::GameOver => "GAMEOVER".to_string(),
+        }
+    }
+
+    pub fn decode(line: &str) -> Result<Message, String> {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next().ok_or("empty message")?;
+        let mut field = || fields.next().ok_or(format!("{}: missing field", kind));
+        let msg = match kind {
+            "JOIN" => Message::Join { name: field()?.to_string() },
+            "SEED" => Message::Seed { seed: parse(field()?)?, player: parse(field()?)? },
+            "INPUT" => {
+                let name = field()?;
+                let command = GameCommand::from_name(name).ok_or(format!("unknown command '{}'", name))?;
+                Message::Input { command, pressed: parse::<u8>(field()?)? != 0 }
+            }
+            "ATTACK" => {
+                let lines: usize = parse(field()?)?;
+                // more than the tallest board only wastes the receiver's memory
+                if lines > MAX_BOARD_SIZE as usize {
+                    return Err(format!("attack of {} lines is more than {}", lines, MAX_BOARD_SIZE));
+                }
+                Message::Attack { lines, hole: parse(field()?)? }
+            }
+            "BOARD" => {
+                let width: i32 = parse(field()?)?;
+                let height: i32 = parse(field()?)?;
+                let cells = field()?.chars().map(Cell::from_char).collect::<Option<Vec<Cell>>>()
+                    .ok_or("bad board cell")?;
+                if !(1..=MAX_BOARD_SIZE).contains(&width) || !(1..=MAX_BOARD_SIZE).contains(&height) {
+                    return Err(format!("board size {}x{} is out of range", width, height));
+                }
+                if width.checked_mul(height) != Some(cells.len() as i32) {
+                    return Err(format!("board size {}x{} does not match {} cells", width, height, cells.len()));
+                }
+                Message::Board(Board { width, height, cells })
+            }
+            "GAMEOVER" => Message::GameOver,
+            _ => return Err(format!("unknown message '{}'", kind)),
+        };
+        Ok(msg)
+    }
+}
+
+fn parse<T: std::str::FromStr>(field: &str) -> Result<T, String> {
+    field.parse().map_err(|_| format!("bad number '{}'", field))
+}
+
+fn send_line(stream: &mut TcpStream, msg: &Message) -> io::Result<()> {
+    writeln!(stream, "{}", msg.encode())
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Message>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Message::decode(line.trim()).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// copy valid messages from one player to the other until either side hangs up
+fn relay(from: TcpStream, mut to: TcpStream) {
+    let reader = BufReader::new(from.try_clone().expect("clone tcp stream"));
+    for line in reader.lines() {
+        let Ok(line) = line else { break };
+        let Ok(msg) = Message::decode(line.trim()) else { continue };
+        if send_line(&mut to, &msg).is_err() {
+            break;
+        }
+    }
+    // wake up the relay going the other way
+    let _ = from.shutdown(Shutdown::Both);
+    let _ = to.shutdown(Shutdown::Both);
+}
+
+// the JOIN a client opens with, an error if it sends anything else or nothing in JOIN_TIMEOUT
+fn read_join(stream: &TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(JOIN_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let join = read_message(&mut reader)?;
+    stream.set_read_timeout(None)?;
+    match join {
+        Some(Message::Join { .. }) => Ok(()),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "expected JOIN")),
+    }
+}
+
+// false for a client that hung up, looked at without reading anything
+fn is_open(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let open = match stream.peek(&mut [0]) {
+        Ok(n) => n > 0,
+        Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+    };
+    stream.set_nonblocking(false).is_ok() && open
+}
+
+// the match between two players that joined: seeds, then relay until one of them leaves
+fn start_match(mut first: TcpStream, mut second: TcpStream, seed: u64) -> io::Result<()> {
+    for (player, stream) in [&mut first, &mut second].into_iter().enumerate() {
+        send_line(stream, &Message::Seed { seed, player })?;
+    }
+    let (first_in, second_in) = (first.try_clone()?, second.try_clone()?);
+    let other = thread::spawn(move || relay(second_in, first));
+    relay(first_in, second);
+    other.join().map_err(|_| io::Error::other("relay thread panicked"))
+}
+
+// run one match between two connected players, returns when one of them leaves.
+pub fn serve_match(first: TcpStream, second: TcpStream, seed: u64) -> io::Result<()> {
+    read_join(&first)?;
+    read_join(&second)?;
+    start_match(first, second, seed)
+}
+
+// pair clients in the order their JOIN comes in, each match on its own thread. Every client
+// joins on a thread of its own so a silent one holds up nobody, and a client that left while
+// waiting for an opponent is dropped instead of paired. Only returns if it cannot go on.
+pub fn run_server(listener: TcpListener) -> io::Result<()> {
+    let (joined_tx, joined) = mpsc::channel::<TcpStream>();
+    thread::spawn(move || {
+        let mut waiting: Option<TcpStream> = None;
+        for stream in joined {
+            match waiting.take() {
+                Some(first) if is_open(&first) => {
+                    thread::spawn(move || {
+                        if let Err(e) = start_match(first, stream, rand::random()) {
+                            eprintln!("match ended: {}", e);
+                        }
+                    });
+                }
+                _ => waiting = Some(stream),
+            }
+        }
+    });
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            // one client failing to connect is no reason to stop serving the others
+            Err(e) => {
+                eprintln!("accept failed: {}", e);
+                thread::sleep(ACCEPT_RETRY);
+                continue;
+            }
+        };
+        let joined = joined_tx.clone();
+        thread::spawn(move || match read_join(&stream) {
+            Ok(()) => {
+                if joined.send(stream).is_err() {
+                    eprintln!("matchmaking stopped");
+                }
+            }
+            Err(e) => eprintln!("client did not join: {}", e),
+        });
+    }
+}
+
+// What the peer is holding down, from the INPUT messages it sends.
+// Feed every received message to `handle`, then poll it like any other source.
+#[derive(Clone, Debug, Default)]
+pub struct PeerInput {
+    down: Vec<GameCommand>,
+}
+
+impl PeerInput {
+    pub fn handle(&mut self, msg: &Message) {
+        match *msg {
+            Message::Input { command, pressed: true } if !self.down.contains(&command) => self.down.push(command),
+            Message::Input { command, pressed: false } => self.down.retain(|&c| c != command),
+            // a peer that topped out lets go of everything
+            Message::GameOver => self.down.clear(),
+            _ => {}
+        }
+    }
+}
+
+impl InputSource for PeerInput {
+    fn poll(&mut self) -> Vec<(bool, GameCommand)> {
+        states_of(&self.down)
+    }
+}
+
+pub struct Client {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+    connected: bool,
+    pub seed: u64,
+    pub player: usize,
+}
+
+impl Client {
+    // connect and wait until the server has found an opponent
+    pub fn connect(addr: impl ToSocketAddrs, name: &str) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        send_line(&mut stream, &Message::Join { name: name.replace(char::is_whitespace, "_") })?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (seed, player) = match read_message(&mut reader)? {
+            Some(Message::Seed { seed, player }) => (seed, player),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "expected SEED")),
+        };
+
+        let (tx, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(Some(msg)) = read_message(&mut reader) {
+                if tx.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { stream, incoming, connected: true, seed, player })
+    }
+
+    pub fn send(&mut self, msg: &Message) -> io::Result<()> {
+        send_line(&mut self.stream, msg)
+    }
+
+    // messages received since the last call, never blocks
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        loop {
+            match self.incoming.try_recv() {
+                Ok(msg) => messages.push(msg),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    // false once the server or the opponent went away
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+// the reader thread holds a clone of the socket, shut it down so the server notices we left
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_encode_decode() {
+        let messages = [
+            Message::Join { name: "alice".to_string() },
+            Message::Seed { seed: 1234567890123, player: 1 },
+            Message::Input { command: GameCommand::HardDrop, pressed: true },
+            Message::Attack { lines: 4, hole: 7 },
+            Message::Board(Board::from_rows(&["..W", "IGG"]).unwrap()),
+            Message::GameOver,
+        ];
+        for msg in messages {
+            assert_eq!(Message::decode(&msg.encode()), Ok(msg));
+        }
+        assert!(Message::decode("BOARD 2 2 ...").is_err());
+        assert!(Message::decode("ATTACK x 1").is_err());
+        assert!(Message::decode("ATTACK 4000000000 1").is_err());
+        assert!(Message::decode("BOARD 100000 100000 ...").is_err());
+        assert!(Message::decode("HELLO").is_err());
+    }
+
+    #[test]
+    fn test_peer_input() {
+        let mut peer = PeerInput::default();
+        peer.handle(&Message::Input { command: GameCommand::MoveLeft, pressed: true });
+        peer.handle(&Message::Input { command: GameCommand::Rotate, pressed: true });
+        peer.handle(&Message::Input { command: GameCommand::Rotate, pressed: false });
+        peer.handle(&Message::Attack { lines: 1, hole: 0 });
+        let states = peer.poll();
+        assert!(states.contains(&(true, GameCommand::MoveLeft)));
+        assert!(states.contains(&(false, GameCommand::Rotate)));
+        // held until released
+        assert!(peer.poll().contains(&(true, GameCommand::MoveLeft)));
+        peer.handle(&Message::GameOver);
+        assert!(peer.poll().iter().all(|&(down, _)| !down));
+    }
+
+    fn wait_for(client: &mut Client) -> Message {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(msg) = client.poll().into_iter().next() {
+                return msg;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("no message received");
+    }
+
+    #[test]
+    fn test_match_on_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (first, _) = listener.accept().unwrap();
+            let (second, _) = listener.accept().unwrap();
+            serve_match(first, second, 99)
+        });
+
+        // connect blocks until both players are there
+        let first = thread::spawn(move || Client::connect(addr, "p1").unwrap());
+        let mut second = Client::connect(addr, "p2").unwrap();
+        let mut first = first.join().unwrap();
+        assert_eq!((first.seed, second.seed), (99, 99));
+        assert_eq!(first.player + second.player, 1);
+
+        first.send(&Message::Attack { lines: 2, hole: 3 }).unwrap();
+        assert_eq!(wait_for(&mut second), Message::Attack { lines: 2, hole: 3 });
+        second.send(&Message::GameOver).unwrap();
+        assert_eq!(wait_for(&mut first), Message::GameOver);
+
+        drop(first);
+        drop(second);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_server_skips_clients_that_left() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || run_server(listener));
+
+        // one that never says JOIN and one that leaves right after it
+        let _silent = TcpStream::connect(addr).unwrap();
+        let mut gone = TcpStream::connect(addr).unwrap();
+        writeln!(gone, "JOIN gone").unwrap();
+        drop(gone);
+        thread::sleep(Duration::from_millis(100));
+
+        // the next two are paired with each other
+        let first = thread::spawn(move || Client::connect(addr, "p1").unwrap());
+        let mut second = Client::connect(addr, "p2").unwrap();
+        let mut first = first.join().unwrap();
+        assert_eq!(first.seed, second.seed);
+        assert_eq!(first.player + second.player, 1);
+        first.send(&Message::GameOver).unwrap();
+        assert_eq!(wait_for(&mut second), Message::GameOver);
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::game::{Board, GameCommand, Pos, Tetromino, apply_command, try_hard_drop};
+
+// the moves a player can make before the final hard drop.
+// SoftDrop is a single row here, like one tap of the key.
+const MOVES: [GameCommand; 4] = [
+    GameCommand::MoveLeft,
+    GameCommand::MoveRight,
+    GameCommand::Rotate,
+    GameCommand::SoftDrop,
+];
+
+// a place where the piece can lock and the shortest input to get it there from the spawn.
+// The commands always end with the HardDrop that locks it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    pub piece: Tetromino,
+    pub commands: Vec<GameCommand>,
+}
+
+impl Path {
+    // reached by moving under an overhang or rotating on the floor,
+    // i.e. a plain drop from above would not get there
+    pub fn is_tuck_or_spin(&self) -> bool {
+        self.commands.contains(&GameCommand::SoftDrop)
+    }
+}
+
+// the squares a locked piece covers, in a fixed order.
+// I, S, Z and O have rotations that cover the same squares, they count as one placement.
+pub fn cells_key(piece: &Tetromino) -> [Pos; 4] {
+    let mut cells = piece.world_cells();
+    cells.sort_by_key(|p| (p.y, p.x));
+    cells
+}
+
+// the search visits the same few hundred positions over and over, a flat table indexed by
+// position and rotation is a lot faster than hashing every Tetromino.
+// Pieces stick out at most 2 squares from their position, MARGIN leaves room for that.
+const MARGIN: i32 = 3;
+
+struct Visited {
+    width: i32,
+    height: i32,
+    // how we got to each visited position: (previous position, command), None for the start
+    came_from: Vec<Option<Option<(Tetromino, GameCommand)>>>,
+}
+
+impl Visited {
+    fn new(board: &Board) -> Self {
+        let width = board.width + 2 * MARGIN;
+        let height = board.height + 2 * MARGIN;
+        Self { width, height, came_from: vec![None; (width * height * 4) as usize] }
+    }
+
+    fn index(&self, t: &Tetromino) -> usize {
+        let Pos { x, y } = t.pos();
+        let (x, y) = (x + MARGIN, y + MARGIN);
+        debug_assert!(x >= 0 && y >= 0 && x < self.width && y < self.height);
+        ((y * self.width + x) * 4) as usize + t.rotation_index()
+    }
+
+    // false if `t` was already visited
+    fn insert(&mut self, t: &Tetromino, from: Option<(Tetromino, GameCommand)>) -> bool {
+        let i = self.index(t);
+        if self.came_from[i].is_some() {
+            return false;
+        }
+        self.came_from[i] = Some(from);
+        true
+    }
+
+    fn get(&self, t: &Tetromino) -> Option<(Tetromino, GameCommand)> {
+        self.came_from[self.index(t)].flatten()
+    }
+
+    // walk the search tree back to the spawn
+    fn commands_to(&self, mut at: Tetromino) -> Vec<GameCommand> {
+        let mut commands = Vec::new();
+        while let Some((previous, command)) = self.get(&at) {
+            commands.push(command);
+            at = previous;
+        }
+        commands.reverse();
+        commands
+    }
+}
+
+// Breadth first search over every position and rotation reachable from `piece`
+// with left/right, rotation (kicks included) and one row soft drops.
+// Each reachable position is hard dropped; the first time a landing spot shows up is
+// also the shortest way to get there, so the result holds one entry per spot.
+pub fn find_paths(board: &Board, piece: &Tetromino) -> Vec<Path> {
+    let mut found = Vec::new();
+    let mut landed = HashSet::new();
+    let mut visited = Visited::new(board);
+    let mut todo = VecDeque::new();
+
+    if !board.can_place(piece) {
+        return found;
+    }
+    visited.insert(piece, None);
+    todo.push_back(*piece);
+
+    while let Some(current) = todo.pop_front() {
+        // after a soft drop the piece lands where its parent would have, only with a longer path
+        let soft_dropped = matches!(visited.get(&current), Some((_, GameCommand::SoftDrop)));
+        if !soft_dropped
+            && let Some(dropped) = try_hard_drop(board, &current)
+            && landed.insert(cells_key(&dropped)) {
+            let mut commands = visited.commands_to(current);
+            commands.push(GameCommand::HardDrop);
+            found.push(Path { piece: dropped, commands });
+        }
+        for command in MOVES {
+            if let Some(next) = apply_command(board, &current, command)
+                && visited.insert(&next, Some((current, command))) {
+                todo.push_back(next);
+            }
+        }
+    }
+    found
+}
+
+// shortest input that locks `piece` on the squares of `target`, if it can get there at all.
+pub fn path_to(board: &Board, piece: &Tetromino, target: &Tetromino) -> Option<Vec<GameCommand>> {
+    let key = cells_key(target);
+    find_paths(board, piece).into_iter()
+        .find(|path| cells_key(&path.piece) == key)
+        .map(|path| path.commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::TetrominoKind;
+
+    // press the commands one by one like GameState does, every one of them has to move the piece
+    fn replay(board: &Board, piece: &Tetromino, commands: &[GameCommand]) -> Tetromino {
+        commands.iter().fold(*piece, |t, &command| apply_command(board, &t, command).unwrap())
+    }
+
+    fn has_cells(path: &Path, cells: &[(i32, i32)]) -> bool {
+        let mut want: Vec<Pos> = cells.iter().map(|&(x, y)| Pos { x, y }).collect();
+        want.sort_by_key(|p| (p.y, p.x));
+        cells_key(&path.piece).to_vec() == want
+    }
+
+    #[test]
+    fn test_paths_replay() {
+        let board = Board::from_rows(&[
+            "..........",
+            "..........",
+            "...GG.....",
+            "G.......GG",
+        ]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::L, Pos { x: 5, y: 1 });
+        let paths = find_paths(&board, &piece);
+        assert!(!paths.is_empty());
+        for path in &paths {
+            assert_eq!(replay(&board, &piece, &path.commands), path.piece);
+            assert_eq!(path.commands.last(), Some(&GameCommand::HardDrop));
+        }
+    }
+
+    #[test]
+    fn test_tuck_under_overhang() {
+        let board = Board::from_rows(&[
+            "..........",
+            "..........",
+            ".....GGGGG",
+            "..........",
+        ]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::I, Pos { x: 5, y: 1 });
+        let paths = find_paths(&board, &piece);
+        let tuck = paths.iter().find(|p| has_cells(p, &[(3, 3), (4, 3), (5, 3), (6, 3)])).unwrap();
+        assert!(tuck.is_tuck_or_spin());
+        assert_eq!(path_to(&board, &piece, &tuck.piece), Some(tuck.commands.clone()));
+    }
+
+    #[test]
+    fn test_t_spin_double() {
+        let board = Board::from_rows(&[
+            "..........",
+            "..........",
+            "..........",
+            "GGG.......",
+            "GG...GGGGG",
+            "GGG.GGGGGG",
+        ]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::T, Pos { x: 5, y: 1 });
+        let paths = find_paths(&board, &piece);
+        let spin = paths.iter().find(|p| has_cells(p, &[(2, 4), (3, 4), (4, 4), (3, 5)])).unwrap();
+        assert_eq!(spin.commands.iter().rev().nth(1), Some(&GameCommand::Rotate));
+        assert_eq!(replay(&board, &piece, &spin.commands), spin.piece);
+    }
+
+    #[test]
+    fn test_blocked_spawn() {
+        let board = Board::from_rows(&["GGGGGGGGGG", "GGGGGGGGGG"]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::O, Pos { x: 5, y: 0 });
+        assert!(find_paths(&board, &piece).is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+// The TGM randomizers, the 7-bag, fixed sequences for practice and a factory picking a generator
+// by name (see generator_by_name).
+//
+// TGM keeps the last 4 pieces dealt and rolls up to N times for a piece that is not among them,
+// keeping the last roll whatever it is. The first piece is never S, Z or O.
+// TGM3 rolls out of a pool of 35 (5 of each piece) instead, and every roll puts the piece that has
+// not shown up for the longest back into the pool, so droughts fix themselves.
+use std::collections::VecDeque;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::classic::NesGenerator;
+use crate::game::{Cell, Pos, RandomGenerator, TetrisGenerator, Tetromino, TetrominoKind};
+use TetrominoKind::*;
+
+pub const TGM_ROLLS: usize = 4;
+pub const TGM2_ROLLS: usize = 6;
+pub const TGM3_ROLLS: usize = 6;
+const FIRST_PIECES: [TetrominoKind; 4] = [I, J, L, T];
+
+pub struct TgmGenerator<R: Rng> {
+    rng: R,
+    // the last 4 pieces, oldest first
+    history: VecDeque<TetrominoKind>,
+    rolls: usize,
+    first: bool,
+}
+
+impl<R: Rng> TgmGenerator<R> {
+    fn with_rng(rng: R, rolls: usize) -> Self {
+        Self { rng, history: VecDeque::from([Z, Z, Z, Z]), rolls: rolls.max(1), first: true }
+    }
+
+    // what the history holds before the first piece, TGM starts with ZZZZ and TGM2 with ZSSZ
+    pub fn with_history(mut self, history: [TetrominoKind; 4]) -> Self {
+        self.history = VecDeque::from(history);
+        self
+    }
+}
+
+impl<R: Rng> TetrisGenerator for TgmGenerator<R> {
+    fn next(&mut self, x: i32, y: i32) -> Tetromino {
+        let kind = if self.first {
+            self.first = false;
+            FIRST_PIECES[self.rng.random_range(0..FIRST_PIECES.len())]
+        } else {
+            let mut kind = Z;
+            for _ in 0..self.rolls {
+                kind = TetrominoKind::ALL[self.rng.random_range(0..7)];
+                if !self.history.contains(&kind) {
+                    break;
+                }
+            }
+            kind
+        };
+        self.history.pop_front();
+        self.history.push_back(kind);
+        Tetromino::new(kind, Pos { x, y })
+    }
+}
+
+impl TgmGenerator<ThreadRng> {
+    pub fn new(rolls: usize) -> Self {
+        Self::with_rng(rand::rng(), rolls)
+    }
+}
+
+// same seed, same piece sequence
+impl TgmGenerator<StdRng> {
+    pub fn with_seed(seed: u64, rolls: usize) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed), rolls)
+    }
+}
+
+pub struct Tgm3Generator<R: Rng> {
+    rng: R,
+    history: VecDeque<TetrominoKind>,
+    pool: Vec<TetrominoKind>,
+    // every piece, the one not seen for the longest first
+    drought: Vec<TetrominoKind>,
+    first: bool,
+}
+
+impl<R: Rng> Tgm3Generator<R> {
+    fn with_rng(rng: R) -> Self {
+        let pool = TetrominoKind::ALL.iter().flat_map(|&k| [k; 5]).collect();
+        // the pieces the history starts with come last, as if they were just dealt
+        let drought = vec![I, O, T, J, L, S, Z];
+        Self { rng, history: VecDeque::from([S, Z, S, Z]), pool, drought, first: true }
+    }
+
+    fn deal(&mut self, kind: TetrominoKind) {
+        self.history.pop_front();
+        self.history.push_back(kind);
+        self.drought.retain(|&k| k != kind);
+        self.drought.push(kind);
+    }
+}
+
+impl<R: Rng> TetrisGenerator for Tgm3Generator<R> {
+    fn next(&mut self, x: i32, y: i32) -> Tetromino {
+        if self.first {
+            self.first = false;
+            let kind = FIRST_PIECES[self.rng.random_range(0..FIRST_PIECES.len())];
+            self.deal(kind);
+            return Tetromino::new(kind, Pos { x, y });
+        }
+        let mut kind = Z;
+        for roll in 0..TGM3_ROLLS {
+            let i = self.rng.random_range(0..self.pool.len());
+            kind = self.pool[i];
+            let last = roll + 1 == TGM3_ROLLS;
+            if !self.history.contains(&kind) || last {
+                self.deal(kind);
+                self.pool[i] = self.drought[0];
+                break;
+            }
+            // a reroll still feeds the longest drought back in
+            self.pool[i] = self.drought[0];
+        }
+        Tetromino::new(kind, Pos { x, y })
+    }
+}
+
+impl Tgm3Generator<ThreadRng> {
+    pub fn new() -> Self {
+        Self::with_rng(rand::rng())
+    }
+}
+
+impl Default for Tgm3Generator<ThreadRng> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tgm3Generator<StdRng> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+}
+
+// the guideline 7-bag: every piece once in a shuffled bag, then the next bag
+pub struct BagGenerator<R: Rng> {
+    rng: R,
+    bag: Vec<TetrominoKind>,
+}
+
+impl<R: Rng> TetrisGenerator for BagGenerator<R> {
+    fn next(&mut self, x: i32, y: i32) -> Tetromino {
+        if self.bag.is_empty() {
+            self.bag = TetrominoKind::ALL.to_vec();
+            self.bag.shuffle(&mut self.rng);
+        }
+        let kind = self.bag.pop().expect("refilled above");
+        Tetromino::new(kind, Pos { x, y })
+    }
+}
+
+impl BagGenerator<ThreadRng> {
+    pub fn new() -> Self {
+        Self { rng: rand::rng(), bag: Vec::new() }
+    }
+}
+
+impl Default for BagGenerator<ThreadRng> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BagGenerator<StdRng> {
+    pub fn with_seed(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), bag: Vec::new() }
+    }
+}
+
+// A fixed piece sequence such as "IOTJLSZ", for drilling an opener or a tricky spot. Once it runs
+// out it starts over, or hands over to another generator (see then).
+pub struct SequenceGenerator {
+    pieces: Vec<TetrominoKind>,
+    next: usize,
+    then: Option<Box<dyn TetrisGenerator>>,
+}
+
+impl SequenceGenerator {
+    // piece letters in any case, spaces, commas and lines starting with '#' are skipped
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.starts_with('#')) {
+            for c in line.chars().filter(|&c| !c.is_whitespace() && c != ',') {
+                match Cell::from_char(c) {
+                    Some(Cell::Piece(kind)) => pieces.push(kind),
+                    _ => return Err(format!("'{}' is not a piece, expected I, O, T, J, L, S or Z", c)),
+                }
+            }
+        }
+        if pieces.is_empty() {
+            return Err("the piece sequence is empty".to_string());
+        }
+        Ok(Self { pieces, next: 0, then: None })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    // deal from `generator` after the last piece instead of looping, a BagGenerator say
+    pub fn then(mut self, generator: Box<dyn TetrisGenerator>) -> Self {
+        self.then = Some(generator);
+        self
+    }
+
+    pub fn pieces(&self) -> &[TetrominoKind] {
+        &self.pieces
+    }
+}
+
+impl TetrisGenerator for SequenceGenerator {
+    fn next(&mut self, x: i32, y: i32) -> Tetromino {
+        if self.next == self.pieces.len() {
+            if let Some(then) = &mut self.then {
+                return then.next(x, y);
+            }
+            self.next = 0;
+        }
+        self.next += 1;
+        Tetromino::new(self.pieces[self.next - 1], Pos { x, y })
+    }
+}
+
+pub const GENERATOR_NAMES: [&str; 6] = ["random", "bag", "nes", "tgm", "tgm2", "tgm3"];
+
+// factory for the command line, like mode::mode_by_name. Without a seed one is picked at random.
+pub fn generator_by_name(name: &str, seed: Option<u64>) -> Option<Box<dyn TetrisGenerator>> {
+    let seed = seed.unwrap_or_else(rand::random);
+    match name.to_ascii_lowercase().as_str() {
+        "random" => Some(Box::new(RandomGenerator::with_seed(seed))),
+        "bag" | "7bag" => Some(Box::new(BagGenerator::with_seed(seed))),
+        "nes" => Some(Box::new(NesGenerator::with_seed(seed))),
+        "tgm" => Some(Box::new(TgmGenerator::with_seed(seed, TGM_ROLLS))),
+        "tgm2" => Some(Box::new(TgmGenerator::with_seed(seed, TGM2_ROLLS).with_history([Z, S, S, Z]))),
+        "tgm3" | "35bag" => Some(Box::new(Tgm3Generator::with_seed(seed))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(generator: &mut dyn TetrisGenerator, n: usize) -> Vec<TetrominoKind> {
+        (0..n).map(|_| generator.next(5, 1).kind).collect()
+    }
+
+    #[test]
+    fn test_first_piece() {
+        for seed in 0..50 {
+            for name in ["tgm", "tgm2", "tgm3"] {
+                let mut generator = generator_by_name(name, Some(seed)).unwrap();
+                assert!(FIRST_PIECES.contains(&generator.next(5, 1).kind));
+            }
+        }
+    }
+
+    #[test]
+    fn test_history_limits_repeats() {
+        for name in ["tgm", "tgm3"] {
+            let a = kinds(generator_by_name(name, Some(3)).unwrap().as_mut(), 700);
+            let b = kinds(generator_by_name(name, Some(3)).unwrap().as_mut(), 700);
+            assert_eq!(a, b);
+            for kind in TetrominoKind::ALL {
+                assert!(a.contains(&kind));
+            }
+            // a piece from the last 4 needs every roll to hit the history: about (4/7)^4 with 4 rolls
+            let again = a.windows(5).filter(|w| w[..4].contains(&w[4])).count();
+            assert!(again < 700 / 6, "{} repeats within 4 pieces with {}", again, name);
+        }
+        // a single roll never looks at the history
+        let mut one_roll = TgmGenerator::with_seed(3, 1);
+        let a = kinds(&mut one_roll, 700);
+        assert!(a.windows(5).filter(|w| w[..4].contains(&w[4])).count() > 700 / 4);
+    }
+
+    #[test]
+    fn test_tgm3_pool_stays_full() {
+        let mut generator = Tgm3Generator::with_seed(9);
+        for _ in 0..500 {
+            generator.next(5, 1);
+            assert_eq!(generator.pool.len(), 35);
+            assert_eq!(generator.drought.len(), 7);
+        }
+    }
+
+    #[test]
+    fn test_generator_names() {
+        for name in GENERATOR_NAMES {
+            assert!(generator_by_name(name, None).is_some());
+        }
+        assert!(generator_by_name("TGM3", Some(1)).is_some());
+        assert!(generator_by_name("bags", Some(1)).is_none());
+    }
+
+    #[test]
+    fn test_bag_deals_every_piece() {
+        let a = kinds(&mut BagGenerator::with_seed(5), 70);
+        for bag in a.chunks(7) {
+            let mut bag = bag.to_vec();
+            bag.sort_by_key(|&k| k as usize);
+            assert_eq!(bag, TetrominoKind::ALL);
+        }
+        assert_eq!(a, kinds(&mut BagGenerator::with_seed(5), 70));
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut looping = SequenceGenerator::parse("i o, t\n# a comment\nZ").unwrap();
+        assert_eq!(looping.pieces(), [I, O, T, Z]);
+        assert_eq!(kinds(&mut looping, 6), [I, O, T, Z, I, O]);
+        // then a bag: the first 7 after the sequence are one of each
+        let mut then_bag = SequenceGenerator::parse("SS").unwrap().then(Box::new(BagGenerator::with_seed(1)));
+        let mut dealt = kinds(&mut then_bag, 9);
+        assert_eq!(dealt[..2], [S, S]);
+        dealt[2..].sort_by_key(|&k| k as usize);
+        assert_eq!(dealt[2..], TetrominoKind::ALL);
+        assert!(SequenceGenerator::parse("IOX").is_err());
+        assert!(SequenceGenerator::parse(" \n# nothing").is_err());
+        assert!(SequenceGenerator::load("/nonexistent/sequence.txt").is_err());
+    }
+}
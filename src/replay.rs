@@ -0,0 +1,211 @@
+// Replay files: the commands held in every frame and when the frame happened, plus what it takes
+// to set up the same game again (seed, board size, mode). GameState only depends on its inputs and
+// the `now` it is handed, so feeding the frames back at the same times plays the same game.
+//
+//     tetris-replay 1
+//     seed 1234
+//     size 10 20
+//     mode sprint
+//     16 -
+//     33 move_left
+//     50 move_left,rotate
+//
+// Frame lines are milliseconds since the start of the game and the held commands ('-' for none).
+use std::io;
+use std::time::Duration;
+
+use crate::game::{GameCommand, check_board_size};
+use crate::mode::{GameMode, seeded_mode_by_name};
+use crate::source::{InputSource, states_of};
+
+pub const REPLAY_MAGIC: &str = "tetris-replay 1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayHeader {
+    pub seed: u64,
+    pub width: i32,
+    pub height: i32,
+    // a name for mode::mode_by_name
+    pub mode: String,
+}
+
+impl ReplayHeader {
+    // the mode of the game, its random garbage (dig) seeded like the pieces so the replay gets the
+    // same holes. None for an unknown name.
+    pub fn game_mode(&self) -> Option<Box<dyn GameMode>> {
+        seeded_mode_by_name(&self.mode, self.seed)
+    }
+}
+
+type Frame = (Duration, Vec<GameCommand>);
+
+fn held(states: &[(bool, GameCommand)]) -> Vec<GameCommand> {
+    states.iter().filter(|&&(down, _)| down).map(|&(_, c)| c).collect()
+}
+
+// collects the frames of a game being played
+pub struct Recorder {
+    header: ReplayHeader,
+    frames: Vec<Frame>,
+}
+
+impl Recorder {
+    pub fn new(header: ReplayHeader) -> Self {
+        Self { header, frames: Vec::new() }
+    }
+
+    // what was fed to the game this frame, `elapsed` after the game started
+    pub fn record(&mut self, elapsed: Duration, states: &[(bool, GameCommand)]) {
+        self.frames.push((elapsed, held(states)));
+    }
+
+    pub fn to_text(&self) -> String {
+        let h = &self.header;
+        let mut text = format!("{}\nseed {}\nsize {} {}\nmode {}\n", REPLAY_MAGIC, h.seed, h.width, h.height, h.mode);
+        for (time, commands) in &self.frames {
+            let names: Vec<&str> = commands.iter().map(GameCommand::name).collect();
+            let names = if names.is_empty() { "-".to_string() } else { names.join(",") };
+            text += &format!("{} {}\n", time.as_millis(), names);
+        }
+        text
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+}
+
+// a recorded game as an InputSource, one frame per poll
+pub struct Replay {
+    pub header: ReplayHeader,
+    frames: Vec<Frame>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+        if lines.next().map(|(_, l)| l) != Some(REPLAY_MAGIC) {
+            return Err("not a replay file".to_string());
+        }
+        let mut field = |name: &str| -> Result<Vec<String>, String> {
+            let (no, line) = lines.next().ok_or(format!("missing '{}'", name))?;
+            let mut words = line.split_whitespace();
+            if words.next() != Some(name) {
+                return Err(format!("line {}: expected '{}'", no, name));
+            }
+            Ok(words.map(str::to_string).collect())
+        };
+        let number = |words: &[String], i: usize| -> Result<i32, String> {
+            words.get(i).and_then(|w| w.parse().ok()).ok_or("bad number in the header".to_string())
+        };
+        let seed = field("seed")?;
+        let seed = seed.first().and_then(|s| s.parse().ok()).ok_or("bad seed")?;
+        let size = field("size")?;
+        let (width, height) = (number(&size, 0)?, number(&size, 1)?);
+        check_board_size(width, height)?;
+        let mode = field("mode")?.join(" ");
+        let header = ReplayHeader { seed, width, height, mode };
+
+        let mut frames = Vec::new();
+        for (no, line) in lines.filter(|(_, l)| !l.is_empty()) {
+            let (time, names) = line.split_once(' ').ok_or(format!("line {}: expected '<ms> <commands>'", no))?;
+            let time: u64 = time.parse().map_err(|_| format!("line {}: bad time '{}'", no, time))?;
+            let commands = names.split(',').filter(|&n| n != "-")
+                .map(|n| GameCommand::from_name(n).ok_or(format!("line {}: unknown command '{}'", no, n)))
+                .collect::<Result<Vec<_>, String>>()?;
+            frames.push((Duration::from_millis(time), commands));
+        }
+        Ok(Self { header, frames, next: 0 })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    // when the frame returned by the last poll happened, pass start + this as `now`
+    pub fn elapsed(&self) -> Duration {
+        self.next.checked_sub(1).map_or(Duration::ZERO, |i| self.frames[i].0)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.frames.len()
+    }
+}
+
+impl InputSource for Replay {
+    fn poll(&mut self) -> Vec<(bool, GameCommand)> {
+        let Some((_, commands)) = self.frames.get(self.next) else {
+            return states_of(&[]);
+        };
+        self.next += 1;
+        states_of(commands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameState, InputState, RandomGenerator, create_custom_game};
+    use crate::source::Scripted;
+    use std::time::Instant;
+    use GameCommand::*;
+
+    fn new_game(header: &ReplayHeader, start: Instant) -> GameState {
+        create_custom_game(header.width, header.height, start, header.game_mode().unwrap(),
+            Box::new(RandomGenerator::with_seed(header.seed))).unwrap()
+    }
+
+    // plays a few pieces, records them and checks the replay ends up in the same game
+    fn record_and_replay(mode: &str) {
+        let header = ReplayHeader { seed: 42, width: 10, height: 20, mode: mode.to_string() };
+        let start = Instant::now();
+        let mut game = new_game(&header, start);
+        let mut recorder = Recorder::new(header.clone());
+        let mut script = Scripted::taps(&[MoveLeft, MoveLeft, HardDrop, Rotate, MoveRight, HardDrop, SoftDrop]);
+        for frame in 1..=100 {
+            let elapsed = Duration::from_millis(frame * 17);
+            let states = script.poll();
+            recorder.record(elapsed, &states);
+            game.update_frame(&InputState::from_states(&states), start + elapsed);
+        }
+
+        let mut replay = Replay::parse(&recorder.to_text()).unwrap();
+        assert_eq!(replay.header, header);
+        // a different start instant does not matter, only the times since the start
+        let start = Instant::now() + Duration::from_secs(5);
+        let mut copy = new_game(&replay.header, start);
+        while !replay.is_finished() {
+            let input = InputState::from_states(&replay.poll());
+            copy.update_frame(&input, start + replay.elapsed());
+        }
+        assert_eq!(copy.board, game.board);
+        assert_eq!(copy.current_tetris, game.current_tetris);
+        assert_eq!(copy.get_stats(), game.get_stats());
+        assert_eq!(game.get_stats().pieces, 2);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        record_and_replay("sprint");
+    }
+
+    #[test]
+    fn test_record_and_replay_dig() {
+        // the garbage holes have to come back in the same columns
+        record_and_replay("dig");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Replay::parse("hello").is_err());
+        let header = format!("{}\nseed 1\nsize 10 20\nmode endless\n", REPLAY_MAGIC);
+        assert!(Replay::parse(&(header.clone() + "16 jump\n")).is_err());
+        assert!(Replay::parse(&(header.clone() + "x -\n")).is_err());
+        assert!(Replay::parse(&header.replace("size 10 20", "size 100000 100000")).is_err());
+        assert!(Replay::parse(&header.replace("size 10 20", "size 4294967306 20")).is_err());
+        let replay = Replay::parse(&(header + "16 -\n33 move_left,rotate\n")).unwrap();
+        assert_eq!(replay.frames[1], (Duration::from_millis(33), vec![MoveLeft, Rotate]));
+    }
+}
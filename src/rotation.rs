@@ -0,0 +1,309 @@
+// Rotation systems: the shape of every piece in each of its 4 orientations and the kick offsets
+// tried when a turn does not fit. A Tetromino carries its system, so everything that moves pieces
+// (apply_command, the path finder, finesse) follows the one the game was set up with.
+//
+// Offsets are around the piece position with y pointing down, orientations go clockwise from the
+// spawn one. Only clockwise turns exist, the game has one rotate key.
+use crate::game::{Board, Pos, Tetromino, TetrominoKind};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RotationSystem {
+    // the guideline Super Rotation System, 5 kicks per turn
+    #[default]
+    Srs,
+    // Arika's TGM system: T, J and L spawn flat side up, kicks one column right then left, with the
+    // center column rule for T, J and L
+    Ars,
+    // the NES one: turns in place, never kicks
+    Nrs,
+}
+
+const fn p(x: i32, y: i32) -> Pos {
+    Pos { x, y }
+}
+
+type Shapes = [[Pos; 4]; 4];
+
+const SRS_I: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(2, 0)],
+    [p(1, -1), p(1, 0), p(1, 1), p(1, 2)],
+    [p(-1, 1), p(0, 1), p(1, 1), p(2, 1)],
+    [p(0, -1), p(0, 0), p(0, 1), p(0, 2)],
+];
+const SRS_T: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(0, -1)],
+    [p(0, -1), p(0, 0), p(1, 0), p(0, 1)],
+    [p(-1, 0), p(0, 0), p(1, 0), p(0, 1)],
+    [p(-1, 0), p(0, 0), p(0, -1), p(0, 1)],
+];
+const SRS_J: Shapes = [
+    [p(-1, -1), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(0, -1), p(1, -1), p(0, 0), p(0, 1)],
+    [p(-1, 0), p(0, 0), p(1, 0), p(1, 1)],
+    [p(0, -1), p(0, 0), p(0, 1), p(-1, 1)],
+];
+const SRS_L: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(1, -1)],
+    [p(0, -1), p(0, 0), p(0, 1), p(1, 1)],
+    [p(-1, 1), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(-1, -1), p(0, -1), p(0, 0), p(0, 1)],
+];
+const SRS_S: Shapes = [
+    [p(-1, 0), p(0, 0), p(0, -1), p(1, -1)],
+    [p(0, -1), p(0, 0), p(1, 0), p(1, 1)],
+    [p(-1, 1), p(0, 1), p(0, 0), p(1, 0)],
+    [p(-1, -1), p(-1, 0), p(0, 0), p(0, 1)],
+];
+const SRS_Z: Shapes = [
+    [p(-1, -1), p(0, -1), p(0, 0), p(1, 0)],
+    [p(1, -1), p(1, 0), p(0, 0), p(0, 1)],
+    [p(-1, 0), p(0, 0), p(0, 1), p(1, 1)],
+    [p(0, -1), p(0, 0), p(-1, 0), p(-1, 1)],
+];
+const O_SHAPE: [Pos; 4] = [p(0, 0), p(1, 0), p(0, 1), p(1, 1)];
+
+// TGM: T, J and L spawn flat side up and their upside down orientation sits on the bottom row of
+// the 3x3 box, I, S and Z only have two orientations.
+const ARS_I: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(2, 0)],
+    [p(1, -1), p(1, 0), p(1, 1), p(1, 2)],
+    [p(-1, 0), p(0, 0), p(1, 0), p(2, 0)],
+    [p(1, -1), p(1, 0), p(1, 1), p(1, 2)],
+];
+const ARS_T: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(0, 1)],
+    [p(0, -1), p(-1, 0), p(0, 0), p(0, 1)],
+    [p(0, 0), p(-1, 1), p(0, 1), p(1, 1)],
+    [p(0, -1), p(0, 0), p(1, 0), p(0, 1)],
+];
+const ARS_J: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(1, 1)],
+    [p(0, -1), p(0, 0), p(-1, 1), p(0, 1)],
+    [p(-1, 0), p(-1, 1), p(0, 1), p(1, 1)],
+    [p(0, -1), p(1, -1), p(0, 0), p(0, 1)],
+];
+const ARS_L: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(-1, 1)],
+    [p(-1, -1), p(0, -1), p(0, 0), p(0, 1)],
+    [p(1, 0), p(-1, 1), p(0, 1), p(1, 1)],
+    [p(0, -1), p(0, 0), p(0, 1), p(1, 1)],
+];
+const ARS_S: Shapes = [
+    [p(0, 0), p(1, 0), p(-1, 1), p(0, 1)],
+    [p(-1, -1), p(-1, 0), p(0, 0), p(0, 1)],
+    [p(0, 0), p(1, 0), p(-1, 1), p(0, 1)],
+    [p(-1, -1), p(-1, 0), p(0, 0), p(0, 1)],
+];
+const ARS_Z: Shapes = [
+    [p(-1, 0), p(0, 0), p(0, 1), p(1, 1)],
+    [p(1, -1), p(0, 0), p(1, 0), p(0, 1)],
+    [p(-1, 0), p(0, 0), p(0, 1), p(1, 1)],
+    [p(1, -1), p(0, 0), p(1, 0), p(0, 1)],
+];
+
+// NES: the same spawn shapes as TGM but T, J and L turn around their center, and the I lies one
+// column further left
+const NRS_I: Shapes = [
+    [p(-2, 0), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(0, -2), p(0, -1), p(0, 0), p(0, 1)],
+    [p(-2, 0), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(0, -2), p(0, -1), p(0, 0), p(0, 1)],
+];
+const NRS_T: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(0, 1)],
+    [p(0, -1), p(-1, 0), p(0, 0), p(0, 1)],
+    [p(0, -1), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(0, -1), p(0, 0), p(1, 0), p(0, 1)],
+];
+const NRS_J: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(1, 1)],
+    [p(0, -1), p(0, 0), p(-1, 1), p(0, 1)],
+    [p(-1, -1), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(0, -1), p(1, -1), p(0, 0), p(0, 1)],
+];
+const NRS_L: Shapes = [
+    [p(-1, 0), p(0, 0), p(1, 0), p(-1, 1)],
+    [p(-1, -1), p(0, -1), p(0, 0), p(0, 1)],
+    [p(1, -1), p(-1, 0), p(0, 0), p(1, 0)],
+    [p(0, -1), p(0, 0), p(0, 1), p(1, 1)],
+];
+const NRS_S: Shapes = [
+    [p(0, 0), p(1, 0), p(-1, 1), p(0, 1)],
+    [p(0, -1), p(0, 0), p(1, 0), p(1, 1)],
+    [p(0, 0), p(1, 0), p(-1, 1), p(0, 1)],
+    [p(0, -1), p(0, 0), p(1, 0), p(1, 1)],
+];
+const NRS_Z: Shapes = [
+    [p(-1, 0), p(0, 0), p(0, 1), p(1, 1)],
+    [p(1, -1), p(0, 0), p(1, 0), p(0, 1)],
+    [p(-1, 0), p(0, 0), p(0, 1), p(1, 1)],
+    [p(1, -1), p(0, 0), p(1, 0), p(0, 1)],
+];
+const NRS_O: [Pos; 4] = [p(-1, 0), p(0, 0), p(-1, 1), p(0, 1)];
+
+// SRS kicks for a clockwise turn out of each orientation, the first one that fits wins
+const SRS_KICKS: [[Pos; 5]; 4] = [
+    [p(0, 0), p(-1, 0), p(-1, -1), p(0, 2), p(-1, 2)],
+    [p(0, 0), p(1, 0), p(1, 1), p(0, -2), p(1, -2)],
+    [p(0, 0), p(1, 0), p(1, -1), p(0, 2), p(1, 2)],
+    [p(0, 0), p(-1, 0), p(-1, 1), p(0, -2), p(-1, -2)],
+];
+const SRS_I_KICKS: [[Pos; 5]; 4] = [
+    [p(0, 0), p(-2, 0), p(1, 0), p(-2, 1), p(1, -2)],
+    [p(0, 0), p(-1, 0), p(2, 0), p(-1, -2), p(2, 1)],
+    [p(0, 0), p(2, 0), p(-1, 0), p(2, -1), p(-1, 2)],
+    [p(0, 0), p(1, 0), p(-2, 0), p(1, 2), p(-2, -1)],
+];
+const ARS_KICKS: [Pos; 3] = [p(0, 0), p(1, 0), p(-1, 0)];
+const NO_KICKS: [Pos; 1] = [p(0, 0)];
+
+impl RotationSystem {
+    pub const ALL: [RotationSystem; 3] = [RotationSystem::Srs, RotationSystem::Ars, RotationSystem::Nrs];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RotationSystem::Srs => "srs",
+            RotationSystem::Ars => "ars",
+            RotationSystem::Nrs => "nrs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RotationSystem> {
+        Self::ALL.into_iter().find(|r| r.name() == name.to_lowercase())
+    }
+
+    // the squares of `kind` in orientation `rotation` (0..4), around the piece position
+    pub fn cells(&self, kind: TetrominoKind, rotation: usize) -> [Pos; 4] {
+        use TetrominoKind::*;
+        let shapes = match (self, kind) {
+            (RotationSystem::Nrs, O) => return NRS_O,
+            (_, O) => return O_SHAPE,
+            (RotationSystem::Srs, I) => &SRS_I,
+            (RotationSystem::Srs, T) => &SRS_T,
+            (RotationSystem::Srs, J) => &SRS_J,
+            (RotationSystem::Srs, L) => &SRS_L,
+            (RotationSystem::Srs, S) => &SRS_S,
+            (RotationSystem::Srs, Z) => &SRS_Z,
+            (RotationSystem::Ars, I) => &ARS_I,
+            (RotationSystem::Ars, T) => &ARS_T,
+            (RotationSystem::Ars, J) => &ARS_J,
+            (RotationSystem::Ars, L) => &ARS_L,
+            (RotationSystem::Ars, S) => &ARS_S,
+            (RotationSystem::Ars, Z) => &ARS_Z,
+            (RotationSystem::Nrs, I) => &NRS_I,
+            (RotationSystem::Nrs, T) => &NRS_T,
+            (RotationSystem::Nrs, J) => &NRS_J,
+            (RotationSystem::Nrs, L) => &NRS_L,
+            (RotationSystem::Nrs, S) => &NRS_S,
+            (RotationSystem::Nrs, Z) => &NRS_Z,
+        };
+        shapes[rotation % 4]
+    }
+
+    // where `kind` appears, from Board::spawn_pos. The NES I comes in a row lower: standing up it
+    // reaches two rows above its center and has to fit right after spawning.
+    pub fn spawn_offset(&self, kind: TetrominoKind) -> Pos {
+        match (self, kind) {
+            (RotationSystem::Nrs, TetrominoKind::I) => p(0, 1),
+            _ => p(0, 0),
+        }
+    }
+
+    // offsets to try, in order, when turning clockwise out of orientation `from`
+    pub fn kicks(&self, kind: TetrominoKind, from: usize) -> &'static [Pos] {
+        use TetrominoKind::*;
+        match (self, kind) {
+            (_, O) | (RotationSystem::Nrs, _) => &NO_KICKS,
+            (RotationSystem::Srs, I) => &SRS_I_KICKS[from % 4],
+            (RotationSystem::Srs, _) => &SRS_KICKS[from % 4],
+            // TGM never kicks the I
+            (RotationSystem::Ars, I) => &NO_KICKS,
+            (RotationSystem::Ars, _) => &ARS_KICKS,
+        }
+    }
+
+    // The ARS center column rule: when a T, J or L turn is blocked and the first blocked square,
+    // read left to right from the top, is in the middle column, the piece does not kick.
+    // `rotated` is the turned piece before any kick.
+    pub(crate) fn can_kick(&self, board: &Board, rotated: &Tetromino) -> bool {
+        use TetrominoKind::*;
+        if *self != RotationSystem::Ars || !matches!(rotated.kind, T | J | L) {
+            return true;
+        }
+        let mut cells = rotated.world_cells();
+        cells.sort_by_key(|c| (c.y, c.x));
+        cells.into_iter().find(|&c| board.is_occupied(c))
+            .is_none_or(|c| c.x != rotated.pos().x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameCommand, apply_command, try_rotate};
+
+    // a piece turned `turns` times clockwise, without kicks
+    fn turned(system: RotationSystem, kind: TetrominoKind, pos: Pos, turns: usize) -> Tetromino {
+        let empty = Board::from_rows(&["......."; 7]).unwrap();
+        let t = Tetromino::new(kind, pos).with_rotation_system(system);
+        (0..turns).fold(t, |t, _| try_rotate(&empty, &t).unwrap())
+    }
+
+    #[test]
+    fn test_srs_wall_kick() {
+        let board = Board::from_rows(&["......."; 5]).unwrap();
+        // an I standing in the left column kicks one to the right to lie flat
+        let standing = turned(RotationSystem::Srs, TetrominoKind::I, Pos { x: 3, y: 2 }, 3);
+        let standing = (0..3).fold(standing, |t, _| apply_command(&board, &t, GameCommand::MoveLeft).unwrap());
+        assert!(apply_command(&board, &standing, GameCommand::MoveLeft).is_none());
+        let flat = apply_command(&board, &standing, GameCommand::Rotate).unwrap();
+        assert_eq!(flat.rotation_index(), 0);
+        assert_eq!(flat.pos(), Pos { x: 1, y: 2 });
+        // the NES rotation does not kick at all
+        let standing = turned(RotationSystem::Nrs, TetrominoKind::I, Pos { x: 2, y: 2 }, 1);
+        let standing = (0..2).fold(standing, |t, _| apply_command(&board, &t, GameCommand::MoveLeft).unwrap());
+        assert!(apply_command(&board, &standing, GameCommand::Rotate).is_none());
+    }
+
+    #[test]
+    fn test_ars_center_column_rule() {
+        // a block left of the T: the turn kicks one to the right
+        let board = Board::from_rows(&[".......", ".......", "..G....", ".......", "......."]).unwrap();
+        let t = turned(RotationSystem::Ars, TetrominoKind::T, Pos { x: 3, y: 2 }, 3);
+        assert!(board.can_place(&t));
+        let kicked = apply_command(&board, &t, GameCommand::Rotate).unwrap();
+        assert_eq!((kicked.rotation_index(), kicked.pos()), (0, Pos { x: 4, y: 2 }));
+        // a block above the middle: no kick, the turn fails
+        let board = Board::from_rows(&[".......", "...G...", ".......", ".......", "......."]).unwrap();
+        let t = Tetromino::new(TetrominoKind::T, Pos { x: 3, y: 2 }).with_rotation_system(RotationSystem::Ars);
+        assert!(board.can_place(&t));
+        assert!(apply_command(&board, &t, GameCommand::Rotate).is_none());
+    }
+
+    #[test]
+    fn test_shapes_are_pieces() {
+        for system in RotationSystem::ALL {
+            for kind in TetrominoKind::ALL {
+                for rotation in 0..4 {
+                    let mut cells = system.cells(kind, rotation);
+                    cells.sort_by_key(|c| (c.y, c.x));
+                    cells.windows(2).for_each(|w| assert_ne!(w[0], w[1], "{:?} {:?} {}", system, kind, rotation));
+                    // four squares touching each other
+                    let touching = cells.iter()
+                        .filter(|a| cells.iter().any(|b| (a.x - b.x).abs() + (a.y - b.y).abs() == 1))
+                        .count();
+                    assert_eq!(touching, 4, "{:?} {:?} {}", system, kind, rotation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        for system in RotationSystem::ALL {
+            assert_eq!(RotationSystem::from_name(system.name()), Some(system));
+        }
+        assert_eq!(RotationSystem::from_name("SRS"), Some(RotationSystem::Srs));
+        assert_eq!(RotationSystem::from_name("tgm"), None);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+
+use minifb::{Key, Window};
+
+use crate::bindings::Bindings;
+use crate::game::GameCommand;
+
+// Something that presses the buttons: a keyboard, a gamepad, a script, a replay, a network peer...
+// Each frame it reports every command it knows about as (pressed, command), the same pairs the
+// frame loop feeds to GameState::update. A command held down is reported pressed every frame.
+pub trait InputSource {
+    fn poll(&mut self) -> Vec<(bool, GameCommand)>;
+}
+
+// several sources on one player: a command is pressed if any of them presses it
+pub fn merge(states: &[Vec<(bool, GameCommand)>]) -> Vec<(bool, GameCommand)> {
+    GameCommand::ALL.iter()
+        .map(|&command| (states.iter().flatten().any(|&(down, c)| down && c == command), command))
+        .collect()
+}
+
+// the commands in `held` pressed, all others released
+pub fn states_of(held: &[GameCommand]) -> Vec<(bool, GameCommand)> {
+    GameCommand::ALL.iter().map(|&command| (held.contains(&command), command)).collect()
+}
+
+// The minifb keyboard. The window is busy drawing between frames, so the keys are read
+// into the source once a frame with `read` and polled after that.
+pub struct Keyboard {
+    bindings: Bindings<Key>,
+    down: Vec<Key>,
+}
+
+impl Keyboard {
+    pub fn new(bindings: Bindings<Key>) -> Self {
+        Self { bindings, down: Vec::new() }
+    }
+
+    pub fn read(&mut self, window: &Window) {
+        self.down = window.get_keys();
+    }
+}
+
+impl InputSource for Keyboard {
+    fn poll(&mut self) -> Vec<(bool, GameCommand)> {
+        self.bindings.states(|key| self.down.contains(&key))
+    }
+}
+
+// A fixed list of frames, each one the commands held during it. Nothing is pressed after the end.
+// Handy for tests and demos.
+pub struct Scripted {
+    frames: VecDeque<Vec<GameCommand>>,
+}
+
+impl Scripted {
+    pub fn new(frames: Vec<Vec<GameCommand>>) -> Self {
+        Self { frames: frames.into() }
+    }
+
+    // each command tapped: pressed for one frame and released the next
+    pub fn taps(commands: &[GameCommand]) -> Self {
+        Self::new(commands.iter().flat_map(|&c| [vec![c], vec![]]).collect())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl InputSource for Scripted {
+    fn poll(&mut self) -> Vec<(bool, GameCommand)> {
+        states_of(&self.frames.pop_front().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GameCommand::*;
+
+    #[test]
+    fn test_merge() {
+        let keyboard = vec![(false, MoveLeft), (true, Rotate)];
+        let pad = vec![(true, MoveLeft), (false, Rotate)];
+        let merged = merge(&[keyboard, pad]);
+        assert!(merged.contains(&(true, MoveLeft)));
+        assert!(merged.contains(&(true, Rotate)));
+        assert!(merged.contains(&(false, HardDrop)));
+        assert_eq!(merged.len(), GameCommand::ALL.len());
+    }
+
+    #[test]
+    fn test_scripted_taps() {
+        let mut script = Scripted::taps(&[MoveLeft, HardDrop]);
+        assert!(script.poll().contains(&(true, MoveLeft)));
+        assert!(script.poll().iter().all(|&(down, _)| !down));
+        assert!(script.poll().contains(&(true, HardDrop)));
+        script.poll();
+        assert!(script.is_finished());
+        assert!(script.poll().iter().all(|&(down, _)| !down));
+    }
+}
@@ -0,0 +1,466 @@
+// Tetris Bot Protocol (TBP): external bots run as a subprocess and talk JSON, one message per line
+// on stdin/stdout. See https://github.com/tetris-bot-protocol/tbp-spec
+//
+// The game and the bot never share a rotation system for sure, so a suggested move is turned into
+// the squares it covers and matched against the spots the path finder can reach here.
+// Each piece is a fresh start/suggest/stop round, so garbage or anything else the bot cannot
+// predict never makes it lose track of the board.
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::bot::Bot;
+use crate::game::{Board, Cell, GameCommand, GameState, Pos, Tetromino, TetrominoKind};
+use crate::pathfind::{cells_key, find_paths};
+
+// TBP boards are 40 rows tall, row 0 at the bottom
+pub const TBP_ROWS: i32 = 40;
+// how long a bot may take to introduce itself
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn piece_name(kind: TetrominoKind) -> &'static str {
+    use TetrominoKind::*;
+    match kind {
+        I => "I",
+        O => "O",
+        T => "T",
+        J => "J",
+        L => "L",
+        S => "S",
+        Z => "Z",
+    }
+}
+
+pub fn piece_from_name(name: &str) -> Option<TetrominoKind> {
+    use TetrominoKind::*;
+    [I, O, T, J, L, S, Z].into_iter().find(|&k| piece_name(k) == name)
+}
+
+fn cell_json(cell: Cell) -> Value {
+    match cell {
+        Cell::Empty => Value::Null,
+        Cell::Piece(kind) => json!(piece_name(kind)),
+        Cell::Garbage | Cell::Wall => json!("G"),
+    }
+}
+
+// bottom row first, padded with empty rows up to TBP_ROWS
+pub fn board_json(board: &Board) -> Value {
+    let rows: Vec<Value> = (0..TBP_ROWS.max(board.height)).map(|row| {
+        let y = board.height - 1 - row;
+        let cells: Vec<Value> = (0..board.width)
+            .map(|x| board.get_cell(Pos { x, y }).map_or(Value::Null, cell_json))
+            .collect();
+        Value::Array(cells)
+    }).collect();
+    Value::Array(rows)
+}
+
+// the state of `game` as a TBP start message. The queue starts with the piece to place.
+pub fn start_message(game: &GameState) -> Value {
+    let mut queue = vec![piece_name(game.current_tetris.kind)];
+    queue.extend(game.get_queue().iter().map(|t| piece_name(t.kind)));
+    let attack = game.get_attack_state();
+    json!({
+        "type": "start",
+        "hold": game.get_hold().map(piece_name),
+        "queue": queue,
+        "combo": attack.combo.unwrap_or(0),
+        "back_to_back": attack.b2b,
+        "board": board_json(&game.board),
+    })
+}
+
+// SRS spawn shapes around the rotation center, y pointing up like TBP
+fn north_cells(kind: TetrominoKind) -> [(i32, i32); 4] {
+    use TetrominoKind::*;
+    match kind {
+        I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        J => [(-1, 1), (-1, 0), (0, 0), (1, 0)],
+        L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    }
+}
+
+// the board squares of a TBP location {type, orientation, x, y}, in this game's coordinates
+pub fn location_cells(location: &Value, height: i32) -> Option<(TetrominoKind, [Pos; 4])> {
+    let kind = piece_from_name(location["type"].as_str()?)?;
+    let orientation = location["orientation"].as_str()?;
+    let turns = ["north", "east", "south", "west"].iter().position(|&o| o == orientation)?;
+    let x = location["x"].as_i64()? as i32;
+    let y = location["y"].as_i64()? as i32;
+    let cells = north_cells(kind).map(|(mut dx, mut dy)| {
+        for _ in 0..turns {
+            // clockwise with y up
+            (dx, dy) = (dy, -dx);
+        }
+        Pos { x: x + dx, y: height - 1 - (y + dy) }
+    });
+    Some((kind, cells))
+}
+
+// inputs that put `piece` on the squares of a suggested location, None if it is another piece
+// or the spot cannot be reached with this game's rotation.
+pub fn commands_for(board: &Board, piece: &Tetromino, location: &Value) -> Option<Vec<GameCommand>> {
+    let (kind, mut cells) = location_cells(location, board.height)?;
+    if kind != piece.kind {
+        return None;
+    }
+    cells.sort_by_key(|p| (p.y, p.x));
+    find_paths(board, piece).into_iter()
+        .find(|path| cells_key(&path.piece) == cells)
+        .map(|path| path.commands)
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// a running bot. Messages from it are read on a thread so polling never blocks the frame loop.
+pub struct TbpBot {
+    writer: Box<dyn Write + Send>,
+    incoming: Receiver<Value>,
+    child: Option<Child>,
+    connected: bool,
+    pub name: String,
+}
+
+impl TbpBot {
+    // start the bot, `command` is split on whitespace: "cold-clear --flag"
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| invalid("empty bot command".to_string()))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let mut bot = Self::from_streams(BufReader::new(stdout), Box::new(stdin))?;
+        bot.child = Some(child);
+        Ok(bot)
+    }
+
+    // info -> rules -> ready handshake over any pair of streams
+    pub(crate) fn from_streams(reader: impl BufRead + Send + 'static, writer: Box<dyn Write + Send>) -> io::Result<Self> {
+        let (tx, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                // anything that is not JSON is the bot talking to itself, skip it
+                if let Ok(msg) = serde_json::from_str::<Value>(&line)
+                    && tx.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut bot = Self { writer, incoming, child: None, connected: true, name: String::new() };
+
+        let info = bot.expect("info")?;
+        bot.name = info["name"].as_str().unwrap_or("unnamed bot").to_string();
+        bot.send(&json!({ "type": "rules" }))?;
+        bot.expect("ready")?;
+        Ok(bot)
+    }
+
+    fn expect(&mut self, kind: &str) -> io::Result<Value> {
+        let msg = self.incoming.recv_timeout(HANDSHAKE_TIMEOUT)
+            .map_err(|_| invalid(format!("bot did not send '{}'", kind)))?;
+        match msg["type"].as_str() {
+            Some(t) if t == kind => Ok(msg),
+            Some("error") => Err(invalid(format!("bot refused: {}", msg["reason"]))),
+            _ => Err(invalid(format!("expected '{}', got {}", kind, msg))),
+        }
+    }
+
+    pub fn send(&mut self, msg: &Value) -> io::Result<()> {
+        writeln!(self.writer, "{}", msg)?;
+        self.writer.flush()
+    }
+
+    // next message from the bot, never blocks
+    pub fn poll(&mut self) -> Option<Value> {
+        match self.incoming.try_recv() {
+            Ok(msg) => Some(msg),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.connected = false;
+                None
+            }
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+impl Drop for TbpBot {
+    fn drop(&mut self) {
+        let _ = self.send(&json!({ "type": "quit" }));
+        if let Some(child) = self.child.as_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+// Plays a game with an external bot, the TBP counterpart of bot::BotPlayer.
+// While the bot thinks the piece just falls; a move the game cannot do falls back to the built in bot.
+pub struct TbpPlayer {
+    bot: TbpBot,
+    fallback: Bot,
+    plan: VecDeque<GameCommand>,
+    held: Option<GameCommand>,
+    planned_for: Option<usize>,
+    // suggest messages not answered yet: the bot answers them in order, only the last one is
+    // for the piece in play, the others are for pieces that locked while it was thinking
+    pending: usize,
+    // a start was sent and not stopped yet
+    in_game: bool,
+}
+
+impl TbpPlayer {
+    pub fn new(bot: TbpBot) -> Self {
+        Self { bot, fallback: Bot::default(), plan: VecDeque::new(), held: None, planned_for: None, pending: 0, in_game: false }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.bot.name
+    }
+
+    // every piece is a new game for the bot: the board here has garbage and holes it does not know about
+    fn ask(&mut self, game: &GameState) -> io::Result<()> {
+        if self.in_game {
+            self.bot.send(&json!({ "type": "stop" }))?;
+        }
+        self.bot.send(&start_message(game))?;
+        self.in_game = true;
+        self.bot.send(&json!({ "type": "suggest" }))?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    // first suggested move that works here and that move, or the built in bot's choice and None.
+    // A move for the piece Hold brings in is played after a hold.
+    fn choose<'a>(&self, game: &GameState, suggestion: &'a Value) -> (Vec<GameCommand>, Option<&'a Value>) {
+        let moves = suggestion["moves"].as_array().map(Vec::as_slice).unwrap_or_default();
+        let swap = game.hold_swap();
+        let suggested = moves.iter().find_map(|m| {
+            let path = commands_for(&game.board, &game.current_tetris, &m["location"]).or_else(|| {
+                let path = commands_for(&game.board, swap.as_ref()?, &m["location"])?;
+                Some([GameCommand::Hold].into_iter().chain(path).collect())
+            })?;
+            Some((path, Some(m)))
+        });
+        suggested.unwrap_or_else(|| {
+            let commands = self.fallback.think(&game.board, &game.current_tetris, &game.get_queue()).map(|p| p.commands);
+            (commands.unwrap_or_else(|| vec![GameCommand::HardDrop]), None)
+        })
+    }
+
+    // (pressed, command) pairs to feed to GameState::update this frame
+    pub fn inputs(&mut self, game: &GameState) -> Vec<(bool, GameCommand)> {
+        if let Some(command) = self.held.take() {
+            return vec![(false, command)];
+        }
+        if !game.get_phase().has_piece() {
+            return Vec::new();
+        }
+        let pieces = game.get_stats().pieces;
+        if self.planned_for != Some(pieces) {
+            self.planned_for = Some(pieces);
+            self.plan.clear();
+            if !self.bot.is_connected() || self.ask(game).is_err() {
+                self.pending = 0;
+                self.plan.extend(self.choose(game, &Value::Null).0);
+            }
+        }
+        if self.pending > 0 {
+            match self.bot.poll() {
+                Some(msg) if msg["type"] == "suggestion" => {
+                    self.pending -= 1;
+                    // older answers are for pieces already gone
+                    if self.pending == 0 {
+                        let (commands, played) = self.choose(game, &msg);
+                        if let Some(m) = played {
+                            let _ = self.bot.send(&json!({ "type": "play", "move": m }));
+                        }
+                        self.plan.extend(commands);
+                    }
+                }
+                Some(_) => {}
+                None if !self.bot.is_connected() => {
+                    self.pending = 0;
+                    self.plan.extend(self.choose(game, &Value::Null).0);
+                }
+                None => {}
+            }
+        }
+        match self.plan.pop_front() {
+            Some(command) => {
+                self.held = Some(command);
+                vec![(true, command)]
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::randomizer::SequenceGenerator;
+    use std::time::Instant;
+
+    #[test]
+    fn test_choose_with_hold() {
+        let player = TbpPlayer::new(fake_bot());
+        let start = Instant::now();
+        let mut game = GameState::new(10, 20, start, Box::new(SequenceGenerator::parse("IO").unwrap()));
+        assert_eq!(start_message(&game)["hold"], Value::Null);
+        // the bot places the O, which is next: hold first
+        let suggestion = json!({ "type": "suggestion", "moves": [
+            { "location": { "type": "O", "orientation": "north", "x": 0, "y": 0 }, "spin": "none" }
+        ]});
+        let (commands, played) = player.choose(&game, &suggestion);
+        assert_eq!(played, Some(&suggestion["moves"][0]));
+        assert_eq!(commands.first(), Some(&GameCommand::Hold));
+        assert_eq!(commands.last(), Some(&GameCommand::HardDrop));
+
+        game.update(true, GameCommand::Hold, start);
+        assert_eq!(start_message(&game)["hold"], "I");
+        assert_eq!(start_message(&game)["queue"][0], "O");
+    }
+
+    #[test]
+    fn test_board_json() {
+        let board = Board::from_rows(&["....", "IG.W"]).unwrap();
+        let rows = board_json(&board);
+        assert_eq!(rows.as_array().unwrap().len(), TBP_ROWS as usize);
+        assert_eq!(rows[0], json!(["I", "G", null, "G"]));
+        assert_eq!(rows[1], json!([null, null, null, null]));
+    }
+
+    #[test]
+    fn test_location_cells() {
+        // T pointing up resting on the floor of a 20 row board
+        let location = json!({ "type": "T", "orientation": "north", "x": 4, "y": 0 });
+        let (kind, cells) = location_cells(&location, 20).unwrap();
+        assert_eq!(kind, TetrominoKind::T);
+        assert_eq!(cells, [Pos { x: 3, y: 19 }, Pos { x: 4, y: 19 }, Pos { x: 5, y: 19 }, Pos { x: 4, y: 18 }]);
+        // I standing up in the first column
+        let location = json!({ "type": "I", "orientation": "east", "x": 0, "y": 2 });
+        let (_, cells) = location_cells(&location, 20).unwrap();
+        assert!(cells.iter().all(|p| p.x == 0 && p.y >= 16));
+        assert!(location_cells(&json!({ "type": "X" }), 20).is_none());
+    }
+
+    #[test]
+    fn test_commands_for_suggestion() {
+        let board = Board::from_rows(&[".........."; 20]).unwrap();
+        let piece = Tetromino::new(TetrominoKind::I, Pos { x: 5, y: 1 });
+        let location = json!({ "type": "I", "orientation": "north", "x": 1, "y": 0 });
+        let commands = commands_for(&board, &piece, &location).unwrap();
+        assert_eq!(commands, vec![GameCommand::MoveLeft; 4].into_iter().chain([GameCommand::HardDrop]).collect::<Vec<_>>());
+        // another piece, see TbpPlayer::choose for holding
+        let location = json!({ "type": "O", "orientation": "north", "x": 1, "y": 0 });
+        assert!(commands_for(&board, &piece, &location).is_none());
+    }
+
+    // a bot on the other end of two pipes, `reply` gives the answers to each message after the handshake
+    fn pipe_bot(mut reply: impl FnMut(&Value) -> Vec<Value> + Send + 'static) -> TbpBot {
+        let (to_bot, from_game) = io::pipe().unwrap();
+        let (to_game, from_bot) = io::pipe().unwrap();
+        thread::spawn(move || {
+            let mut out = from_bot;
+            writeln!(out, "{}", json!({ "type": "info", "name": "corner", "version": "1", "author": "test", "features": [] })).unwrap();
+            for line in BufReader::new(to_bot).lines() {
+                let msg: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                let replies = match msg["type"].as_str().unwrap() {
+                    "rules" => vec![json!({ "type": "ready" })],
+                    "quit" => break,
+                    _ => reply(&msg),
+                };
+                for r in replies {
+                    writeln!(out, "{}", r).unwrap();
+                }
+            }
+        });
+        TbpBot::from_streams(BufReader::new(to_game), Box::new(from_game)).unwrap()
+    }
+
+    fn suggest_i(x: i32, y: i32) -> Value {
+        json!({ "type": "suggestion", "moves": [
+            { "location": { "type": "I", "orientation": "north", "x": x, "y": y }, "spin": "none" }
+        ]})
+    }
+
+    // always puts the I flat in the bottom left corner
+    fn fake_bot() -> TbpBot {
+        pipe_bot(|msg| if msg["type"] == "suggest" { vec![suggest_i(1, 0)] } else { Vec::new() })
+    }
+
+    // plays a game with `player` until `pieces` have locked
+    fn play_until(player: &mut TbpPlayer, game: &mut GameState, start: Instant, pieces: usize) {
+        // the bot answers on another thread, give it real time to do so
+        let mut frame = 0;
+        while game.get_stats().pieces < pieces && frame < 2000 {
+            frame += 1;
+            thread::sleep(Duration::from_millis(1));
+            let now = start + Duration::from_millis(frame);
+            for (pressed, command) in player.inputs(game) {
+                game.update(pressed, command, now);
+            }
+        }
+    }
+
+    #[test]
+    fn test_play_with_fake_bot() {
+        let bot = fake_bot();
+        assert_eq!(bot.name, "corner");
+        let mut player = TbpPlayer::new(bot);
+        let start = Instant::now();
+        let mut game = GameState::new(10, 20, start, Box::new(SequenceGenerator::parse("I").unwrap()));
+        play_until(&mut player, &mut game, start, 1);
+        for x in 0..4 {
+            assert_eq!(game.board.get_cell(Pos { x, y: 19 }), Some(Cell::Piece(TetrominoKind::I)));
+        }
+    }
+
+    #[test]
+    fn test_late_suggestion_is_dropped() {
+        // a slow bot: it answers the first suggest only once the second one came,
+        // the first for the corner and the second on the right
+        let mut suggests = 0;
+        let mut player = TbpPlayer::new(pipe_bot(move |msg| {
+            if msg["type"] != "suggest" {
+                return Vec::new();
+            }
+            suggests += 1;
+            if suggests == 2 { vec![suggest_i(1, 0), suggest_i(7, 1)] } else { Vec::new() }
+        }));
+        let start = Instant::now();
+        let mut game = GameState::new(10, 20, start, Box::new(SequenceGenerator::parse("I").unwrap()));
+        // the first piece locks before the bot answers
+        assert!(player.inputs(&game).is_empty());
+        game.update(true, GameCommand::HardDrop, start);
+        game.update(false, GameCommand::HardDrop, start);
+        play_until(&mut player, &mut game, start, 2);
+        assert_eq!(game.get_stats().pieces, 2);
+        // the second piece went where the second answer said, not in the corner
+        assert_eq!(game.board.get_cell(Pos { x: 0, y: 19 }), Some(Cell::Empty));
+        for x in 6..10 {
+            assert_eq!(game.board.get_cell(Pos { x, y: 18 }), Some(Cell::Piece(TetrominoKind::I)));
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::time::Instant;
+
+use crate::game::{GameCommand, GameState, InputState, create_new_game};
+use crate::garbage::GarbageGenerator;
+use crate::mode::ModeStatus;
+
+// where the holes of received garbage go
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HolePolicy {
+    // all rows of one attack share a hole; between attacks the hole moves with this chance (0..1)
+    PerAttack(f64),
+    // every row gets a new hole column
+    PerRow,
+}
+
+impl Default for HolePolicy {
+    fn default() -> Self {
+        HolePolicy::PerAttack(0.7)
+    }
+}
+
+struct Player {
+    game: GameState,
+    // hole columns of the garbage this player receives
+    holes: GarbageGenerator,
+}
+
+impl Player {
+    fn new(game: GameState, policy: HolePolicy) -> Self {
+        let messiness = match policy {
+            HolePolicy::PerAttack(messiness) => messiness,
+            HolePolicy::PerRow => 1.0,
+        };
+        Self { game, holes: GarbageGenerator::new(messiness) }
+    }
+
+    fn receive(&mut self, lines: usize, policy: HolePolicy) {
+        let width = self.game.board.width;
+        match policy {
+            HolePolicy::PerAttack(_) => self.game.receive_garbage(lines, self.holes.next_hole(width)),
+            HolePolicy::PerRow => (0..lines).for_each(|_| self.game.receive_garbage(1, self.holes.next_hole(width))),
+        }
+    }
+}
+
+// Two games on one machine sending garbage to each other.
+// Feed each player's input with update() or update_frame(), then call exchange() once per frame.
+pub struct Versus {
+    players: [Player; 2],
+    policy: HolePolicy,
+}
+
+impl Versus {
+    // an error for a board size out of range, see check_board_size
+    pub fn new(width: i32, height: i32, now: Instant, policy: HolePolicy) -> Result<Self, String> {
+        Ok(Self::with_games(create_new_game(width, height, now)?, create_new_game(width, height, now)?, policy))
+    }
+
+    pub(crate) fn with_games(first: GameState, second: GameState, policy: HolePolicy) -> Self {
+        Self { players: [Player::new(first, policy), Player::new(second, policy)], policy }
+    }
+
+    pub fn update(&mut self, player: usize, press: bool, command: GameCommand, now: Instant) -> bool {
+        self.players[player].game.update(press, command, now)
+    }
+
+    pub fn update_frame(&mut self, player: usize, input: &InputState, now: Instant) {
+        self.players[player].game.update_frame(input, now);
+    }
+
+    // deliver what each player sent (already cancelled against their own pending garbage)
+    // to the other one's queue.
+    pub fn exchange(&mut self) {
+        for i in 0..2 {
+            let sent = self.players[i].game.take_attack();
+            if sent > 0 && !self.is_over() {
+                self.players[1 - i].receive(sent, self.policy);
+            }
+        }
+    }
+
+    pub fn game(&self, player: usize) -> &GameState {
+        &self.players[player].game
+    }
+
+    pub fn game_mut(&mut self, player: usize) -> &mut GameState {
+        &mut self.players[player].game
+    }
+
+    // lines of garbage waiting for `player`
+    pub fn pending(&self, player: usize) -> usize {
+        self.players[player].game.pending_garbage()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.players.iter().any(|p| p.game.get_game_over())
+    }
+
+    // the player still standing once the other one topped out, None while playing or on a draw.
+    pub fn winner(&self) -> Option<usize> {
+        let lost = |i: usize| self.players[i].game.get_status() == ModeStatus::Failed;
+        match (lost(0), lost(1)) {
+            (true, false) => Some(1),
+            (false, true) => Some(0),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Cell, GameState};
+    use crate::randomizer::SequenceGenerator;
+
+    fn versus(now: Instant) -> Versus {
+        Versus::with_games(
+            GameState::new(10, 10, now, Box::new(SequenceGenerator::parse("I").unwrap())),
+            GameState::new(10, 10, now, Box::new(SequenceGenerator::parse("I").unwrap())),
+            HolePolicy::PerAttack(0.0),
+        )
+    }
+
+    #[test]
+    fn test_tetris_sends_garbage() {
+        let now = Instant::now();
+        let mut versus = versus(now);
+        // 4 rows full except column 6, where the vertical I goes, plus one block so it's no perfect clear
+        let board = &mut versus.game_mut(0).board;
+        board.cells[5 * 10] = Cell::Garbage;
+        for y in 6..10 {
+            for x in (0..10).filter(|&x| x != 6) {
+                board.cells[y * 10 + x] = Cell::Garbage;
+            }
+        }
+        versus.update(0, true, GameCommand::Rotate, now);
+        versus.update(0, true, GameCommand::HardDrop, now);
+        versus.exchange();
+        assert_eq!(versus.game(0).get_stats().lines, 4);
+        assert_eq!(versus.pending(1), 4);
+        assert_eq!(versus.game(1).board.garbage_rows(), 0);
+
+        // player 2 locks without clearing and receives it
+        versus.update(1, true, GameCommand::HardDrop, now);
+        versus.exchange();
+        assert_eq!(versus.pending(1), 0);
+        assert_eq!(versus.game(1).board.garbage_rows(), 4);
+        assert_eq!(versus.winner(), None);
+    }
+
+    #[test]
+    fn test_garbage_tops_out() {
+        let now = Instant::now();
+        let mut versus = versus(now);
+        versus.players[1].receive(9, HolePolicy::PerRow);
+        versus.update(1, true, GameCommand::HardDrop, now);
+        versus.exchange();
+        assert!(versus.is_over());
+        assert_eq!(versus.winner(), Some(0));
+    }
+}